@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS stock_search_name_trgm_idx;
+ALTER TABLE stock DROP COLUMN IF EXISTS search_name;
@@ -0,0 +1,5 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS search_name VARCHAR NOT NULL DEFAULT '';
+-- Строку поиска остаткам, загруженным до нее, заполняет приложение после миграций:
+-- нормализация есть только в Rust, см. StockStorage::fill_search_names
+CREATE INDEX IF NOT EXISTS stock_search_name_trgm_idx ON stock USING GIN (search_name gin_trgm_ops);
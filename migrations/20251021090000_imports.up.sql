@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS imports
+(
+    id          UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    supplier    VARCHAR     NOT NULL,
+    source      VARCHAR     NOT NULL,
+    items       BIGINT      NOT NULL DEFAULT 0,
+    error       VARCHAR,
+    mail_uids   BIGINT[]    NOT NULL DEFAULT '{}',
+    mail_action VARCHAR,
+    created     TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS imports_supplier_created_idx ON imports (supplier, created DESC);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS raw_files
+(
+    sha256   VARCHAR PRIMARY KEY NOT NULL,
+    supplier VARCHAR     NOT NULL,
+    source   VARCHAR     NOT NULL,
+    filename VARCHAR     NOT NULL,
+    size     BIGINT      NOT NULL,
+    received TIMESTAMPTZ NOT NULL,
+    content  BYTEA       NOT NULL,
+    created  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS raw_files_supplier_received_idx ON raw_files (supplier, received DESC);
@@ -0,0 +1 @@
+ALTER TABLE imports ADD COLUMN IF NOT EXISTS superseded_by UUID REFERENCES imports (id) ON DELETE SET NULL;
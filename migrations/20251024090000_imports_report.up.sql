@@ -0,0 +1 @@
+ALTER TABLE imports ADD COLUMN IF NOT EXISTS report JSONB;
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS stock_article_idx;
+ALTER TABLE stock DROP COLUMN IF EXISTS article;
+ALTER TABLE stock DROP COLUMN IF EXISTS width;
+ALTER TABLE stock DROP COLUMN IF EXISTS color;
+ALTER TABLE stock DROP COLUMN IF EXISTS collection;
+ALTER TABLE stock DROP COLUMN IF EXISTS brand;
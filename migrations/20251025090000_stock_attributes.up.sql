@@ -0,0 +1,6 @@
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS brand VARCHAR;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS collection VARCHAR;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS color VARCHAR;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS width DOUBLE PRECISION;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS article VARCHAR;
+CREATE INDEX IF NOT EXISTS stock_article_idx ON stock (upper(article));
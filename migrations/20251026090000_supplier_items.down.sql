@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS stock_item_id_idx;
+ALTER TABLE stock DROP COLUMN IF EXISTS item_id;
+DROP TABLE IF EXISTS supplier_items;
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS supplier_items
+(
+    id         UUID PRIMARY KEY NOT NULL,
+    supplier   VARCHAR          NOT NULL,
+    name       VARCHAR          NOT NULL,
+    brand      VARCHAR,
+    collection VARCHAR,
+    color      VARCHAR,
+    width      DOUBLE PRECISION,
+    article    VARCHAR,
+    first_seen TIMESTAMPTZ      NOT NULL DEFAULT now(),
+    last_seen  TIMESTAMPTZ      NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS supplier_items_supplier_idx ON supplier_items (supplier, name);
+-- Случайные идентификаторы нужны только строкам, загруженным до каталога: после
+-- миграций приложение заменяет их идентификаторами товаров, см. StockStorage::link_items
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS item_id UUID NOT NULL DEFAULT uuid_generate_v4();
+ALTER TABLE stock ALTER COLUMN item_id DROP DEFAULT;
+CREATE INDEX IF NOT EXISTS stock_item_id_idx ON stock (item_id);
@@ -0,0 +1,4 @@
+ALTER TABLE stock DROP COLUMN IF EXISTS arrival;
+ALTER TABLE stock DROP COLUMN IF EXISTS incoming;
+ALTER TABLE stock DROP COLUMN IF EXISTS reserved;
+ALTER TABLE stock DROP COLUMN IF EXISTS on_hand;
@@ -0,0 +1,4 @@
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS on_hand DOUBLE PRECISION;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS reserved DOUBLE PRECISION;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS incoming DOUBLE PRECISION;
+ALTER TABLE stock ADD COLUMN IF NOT EXISTS arrival DATE;
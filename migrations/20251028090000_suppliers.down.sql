@@ -0,0 +1,3 @@
+ALTER TABLE supplier_items DROP CONSTRAINT IF EXISTS supplier_items_supplier_fkey;
+ALTER TABLE stock DROP CONSTRAINT IF EXISTS stock_supplier_fkey;
+DROP TABLE IF EXISTS suppliers;
@@ -0,0 +1,47 @@
+CREATE TABLE IF NOT EXISTS suppliers
+(
+    code           VARCHAR PRIMARY KEY NOT NULL,
+    name           VARCHAR     NOT NULL,
+    emails         VARCHAR[]   NOT NULL DEFAULT '{}',
+    contacts       VARCHAR,
+    source         VARCHAR     NOT NULL DEFAULT 'mail',
+    lead_time_days INTEGER,
+    -- Вложения без имени тоже остатки, так присылает Опус
+    unnamed_attachments BOOLEAN NOT NULL DEFAULT FALSE,
+    enabled        BOOLEAN     NOT NULL DEFAULT TRUE,
+    created        TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated        TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+-- Миграции применяются при каждом запуске, поэтому справочник заполняется только
+-- пустым: удаленные через API поставщики не возвращаются
+INSERT INTO suppliers (code, name, emails, source, unnamed_attachments)
+SELECT code, name, emails, source, unnamed_attachments
+FROM (VALUES ('opus', 'Опус-Контракт', '{vvolodin@opuscontract.ru}'::VARCHAR[], 'mail', TRUE),
+             ('fox', 'Братец Лис', '{sales@bratec-lis.com}', 'mail', FALSE),
+             ('fancy', 'Фэнси', '{rassilka@fancyfloor.ru}', 'mail', FALSE),
+             ('carpetland', 'Carpetland', '{ulyana.boyko@carpetland.ru}', 'mail', FALSE),
+             ('zefir', 'Зефир', '{dealer@kover-zefir.ru}', 'mail', FALSE),
+             ('fenix', 'Феникс', '{almaz2008@yandex.ru}', 'mail', FALSE),
+             ('vvk', 'ВВК', '{}', 'web', FALSE),
+             ('ortgraph', 'Ортграф', '{}', 'web', FALSE),
+             ('sportflooring', 'SportFlooring', '{}', 'web', FALSE)) AS seed (code, name, emails, source, unnamed_attachments)
+WHERE NOT EXISTS (SELECT 1 FROM suppliers);
+-- Поставщики, которые уже есть в остатках, но не в справочнике
+INSERT INTO suppliers (code, name)
+SELECT DISTINCT supplier, supplier FROM stock
+UNION
+SELECT DISTINCT supplier, supplier FROM supplier_items
+ON CONFLICT (code) DO NOTHING;
+DO
+$$
+    BEGIN
+        IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'stock_supplier_fkey') THEN
+            ALTER TABLE stock
+                ADD CONSTRAINT stock_supplier_fkey FOREIGN KEY (supplier) REFERENCES suppliers (code) ON UPDATE CASCADE;
+        END IF;
+        IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'supplier_items_supplier_fkey') THEN
+            ALTER TABLE supplier_items
+                ADD CONSTRAINT supplier_items_supplier_fkey FOREIGN KEY (supplier) REFERENCES suppliers (code) ON UPDATE CASCADE;
+        END IF;
+    END
+$$;
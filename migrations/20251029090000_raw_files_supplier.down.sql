@@ -0,0 +1,4 @@
+DELETE FROM raw_files r
+WHERE EXISTS (SELECT 1 FROM raw_files o WHERE o.sha256 = r.sha256 AND o.supplier < r.supplier);
+ALTER TABLE raw_files DROP CONSTRAINT IF EXISTS raw_files_pkey;
+ALTER TABLE raw_files ADD CONSTRAINT raw_files_pkey PRIMARY KEY (sha256);
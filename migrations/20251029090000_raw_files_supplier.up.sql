@@ -0,0 +1,14 @@
+-- Одинаковое содержимое может прийти от разных поставщиков, файл хранится у каждого
+DO
+$$
+BEGIN
+    IF NOT EXISTS (SELECT 1
+                   FROM pg_constraint c
+                            JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = ANY (c.conkey)
+                   WHERE c.conname = 'raw_files_pkey'
+                     AND a.attname = 'supplier') THEN
+        ALTER TABLE raw_files DROP CONSTRAINT IF EXISTS raw_files_pkey;
+        ALTER TABLE raw_files ADD CONSTRAINT raw_files_pkey PRIMARY KEY (sha256, supplier);
+    END IF;
+END
+$$;
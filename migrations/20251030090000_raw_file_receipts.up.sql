@@ -0,0 +1,15 @@
+-- Какие файлы пришли в каждом получении: неизменившийся файл хранится один раз,
+-- но входит во все получения, с которыми пришел
+CREATE TABLE IF NOT EXISTS raw_file_receipts
+(
+    sha256   VARCHAR     NOT NULL,
+    supplier VARCHAR     NOT NULL,
+    received TIMESTAMPTZ NOT NULL,
+    filename VARCHAR     NOT NULL,
+    PRIMARY KEY (supplier, received, sha256),
+    FOREIGN KEY (sha256, supplier) REFERENCES raw_files (sha256, supplier) ON DELETE CASCADE
+);
+INSERT INTO raw_file_receipts (sha256, supplier, received, filename)
+SELECT sha256, supplier, received, filename
+FROM raw_files
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS supplier_items_search_name_trgm_idx;
+ALTER TABLE supplier_items DROP COLUMN IF EXISTS search_name;
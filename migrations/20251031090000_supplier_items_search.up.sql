@@ -0,0 +1,4 @@
+ALTER TABLE supplier_items ADD COLUMN IF NOT EXISTS search_name VARCHAR NOT NULL DEFAULT '';
+-- Строку поиска товарам, добавленным до нее, заполняет приложение после миграций:
+-- нормализация есть только в Rust, см. StockStorage::fill_search_names
+CREATE INDEX IF NOT EXISTS supplier_items_search_name_trgm_idx ON supplier_items USING GIN (search_name gin_trgm_ops);
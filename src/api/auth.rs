@@ -0,0 +1,34 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+
+use super::AppState;
+use crate::{AppError, Result};
+
+/// Пропускает изменяющие запросы и запросы файлов поставщиков и загрузок только
+/// с токеном API в `Authorization: Bearer`. Если токен не настроен, такие запросы
+/// отключены.
+pub async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let Some(token) = state.api_token.as_deref() else {
+        return Err(AppError::Unauthorized(
+            "Токен API не настроен, запрос отключен".to_string(),
+        ));
+    };
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+    if provided != Some(token) {
+        return Err(AppError::Unauthorized("Неверный токен API".to_string()));
+    }
+    Ok(next.run(request).await)
+}
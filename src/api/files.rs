@@ -0,0 +1,139 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use crate::{
+    models::{ParseReport, RawFile, Stock},
+    stock_service, AppError, Result,
+};
+
+const DEFAULT_LIMIT: i32 = 50;
+const MAX_LIMIT: i32 = 500;
+
+#[derive(Deserialize)]
+pub struct FilesQuery {
+    supplier: Option<String>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct FileQuery {
+    /// Поставщик, если такой же файл есть у нескольких
+    supplier: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReprocessQuery {
+    supplier: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct Reprocessed {
+    file: RawFile,
+    /// Все файлы того же получения, разобранные вместе
+    files: Vec<RawFile>,
+    saved: bool,
+    items: usize,
+    /// Строки остатков, только для пробного запуска
+    rows: Option<Vec<Stock>>,
+    report: ParseReport,
+}
+
+/// Сохраненные файлы остатков, новые первыми
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<FilesQuery>,
+) -> Result<Json<Vec<RawFile>>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or_default().max(0);
+    let supplier = query
+        .supplier
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let result = state.import_storage.files(supplier, limit, offset).await?;
+    Ok(Json(result))
+}
+
+/// Скачать сохраненный файл
+pub async fn download(
+    State(state): State<AppState>,
+    Path(sha256): Path<String>,
+    Query(query): Query<FileQuery>,
+) -> Result<impl IntoResponse> {
+    let file = find(&state, &sha256, query.supplier.as_deref()).await?;
+    let content = state.import_storage.content(&file).await?;
+    let disposition = format!(
+        "attachment; filename*=UTF-8''{}",
+        utf8_percent_encode(&file.filename)
+    );
+    let headers = [
+        (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+        (header::CONTENT_DISPOSITION, disposition),
+    ];
+    Ok((headers, content))
+}
+
+/// Разобрать сохраненный файл заново вместе с остальными файлами того же
+/// получения. С `dry_run=true` остатки только возвращаются, без `dry_run`
+/// остатки поставщика заменяются остатками из файлов.
+pub async fn reprocess(
+    State(state): State<AppState>,
+    Path(sha256): Path<String>,
+    Query(query): Query<ReprocessQuery>,
+) -> Result<Json<Reprocessed>> {
+    let file = find(&state, &sha256, query.supplier.as_deref()).await?;
+    let (files, parsed) = stock_service::reprocess(
+        &file,
+        &state.stock_storage,
+        &state.import_storage,
+        query.dry_run,
+    )
+    .await?;
+    let result = Reprocessed {
+        file,
+        files,
+        saved: !query.dry_run && !parsed.items.is_empty(),
+        items: parsed.items.len(),
+        rows: query.dry_run.then_some(parsed.items),
+        report: parsed.report,
+    };
+    Ok(Json(result))
+}
+
+/// Сохраненный файл. Если такое же содержимое пришло от нескольких поставщиков,
+/// нужно указать поставщика
+async fn find(state: &AppState, sha256: &str, supplier: Option<&str>) -> Result<RawFile> {
+    let supplier = supplier.map(str::trim).filter(|s| !s.is_empty());
+    let mut files = state.import_storage.file(sha256, supplier).await?;
+    match files.len() {
+        0 => Err(AppError::NotFound(format!("Файл {sha256} не найден"))),
+        1 => Ok(files.remove(0)),
+        _ => Err(AppError::BadRequest(format!(
+            "Файл {sha256} есть у нескольких поставщиков ({}), укажите supplier",
+            files
+                .iter()
+                .map(|f| f.supplier.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+fn utf8_percent_encode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
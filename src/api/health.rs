@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+use super::AppState;
+use crate::health::RunStatus;
+
+#[derive(Serialize)]
+pub struct Live {
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Ready {
+    status: &'static str,
+    database: Check,
+    migration_version: Option<i64>,
+    components: BTreeMap<&'static str, Component>,
+    jobs: BTreeMap<&'static str, RunStatus>,
+}
+
+#[derive(Serialize)]
+pub struct Check {
+    ok: bool,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Component {
+    enabled: bool,
+    #[serde(flatten)]
+    check: Option<RunStatus>,
+}
+
+/// Процесс жив и отвечает на запросы
+pub async fn live() -> Json<Live> {
+    Json(Live { status: "ok" })
+}
+
+/// Готовность принимать запросы: 503, если недоступна база данных. Ошибки
+/// интеграций и фоновых задач переводят статус в `degraded`, но не в 503.
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<Ready>) {
+    let database = match state.system_storage.ping().await {
+        Ok(()) => Check {
+            ok: true,
+            error: None,
+        },
+        Err(e) => Check {
+            ok: false,
+            error: Some(e.to_string()),
+        },
+    };
+    let migration_version = if database.ok {
+        state
+            .system_storage
+            .migration_version()
+            .await
+            .unwrap_or_default()
+    } else {
+        None
+    };
+    let checks = state.status.checks().await;
+    let jobs = state.status.jobs().await;
+    let components = state
+        .components
+        .iter()
+        .map(|(name, enabled)| {
+            let component = Component {
+                enabled: *enabled,
+                check: checks.get(name).cloned(),
+            };
+            (*name, component)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let failing = components
+        .values()
+        .filter_map(|c| c.check.as_ref())
+        .chain(jobs.values())
+        .any(RunStatus::is_failing);
+    let (code, status) = if !database.ok || migration_version.is_none() {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    } else if failing {
+        (StatusCode::OK, "degraded")
+    } else {
+        (StatusCode::OK, "ok")
+    };
+    let ready = Ready {
+        status,
+        database,
+        migration_version,
+        components,
+        jobs,
+    };
+    (code, Json(ready))
+}
@@ -0,0 +1,34 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+
+use super::AppState;
+use crate::{models::Import, Result};
+
+const DEFAULT_LIMIT: i32 = 50;
+const MAX_LIMIT: i32 = 500;
+
+#[derive(Deserialize)]
+pub struct ImportsQuery {
+    supplier: Option<String>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+}
+
+/// Загрузки остатков с отчетами разбора, новые первыми
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<ImportsQuery>,
+) -> Result<Json<Vec<Import>>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or_default().max(0);
+    let supplier = query
+        .supplier
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let result = state.import_storage.list(supplier, limit, offset).await?;
+    Ok(Json(result))
+}
@@ -0,0 +1,68 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use crate::{
+    models::{Stock, SupplierItem},
+    storage::StockFilter,
+    AppError, Result,
+};
+
+const DEFAULT_LIMIT: i32 = 50;
+const MAX_LIMIT: i32 = 500;
+
+#[derive(Deserialize)]
+pub struct ItemsQuery {
+    supplier: Option<String>,
+    search: Option<String>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ItemDetails {
+    #[serde(flatten)]
+    item: SupplierItem,
+    /// Строки текущих остатков товара, пустые, если товара сейчас нет у поставщика
+    stock: Vec<Stock>,
+}
+
+/// Каталог товаров поставщиков, недавно виденные первыми
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<ItemsQuery>,
+) -> Result<Json<Vec<SupplierItem>>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or_default().max(0);
+    let supplier = query
+        .supplier
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let result = state
+        .stock_storage
+        .items(supplier, query.search.as_deref(), limit, offset)
+        .await?;
+    Ok(Json(result))
+}
+
+/// Товар каталога с текущими остатками
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<ItemDetails>> {
+    let item = state
+        .stock_storage
+        .item(id)
+        .await?
+        .ok_or(AppError::NotFound(format!("Товар {id} не найден")))?;
+    let filter = StockFilter {
+        item_id: Some(id),
+        ..Default::default()
+    };
+    let stock = state.stock_storage.find(&filter, MAX_LIMIT, 0).await?;
+    Ok(Json(ItemDetails { item, stock }))
+}
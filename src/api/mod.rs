@@ -0,0 +1,86 @@
+mod auth;
+mod files;
+mod health;
+mod imports;
+mod items;
+mod stock;
+mod suppliers;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
+use tower_http::trace::TraceLayer;
+
+use crate::{
+    health::Status,
+    storage::{ImportStorage, StockStorage, SupplierStorage, SystemStorage},
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub stock_storage: Arc<StockStorage>,
+    pub import_storage: Arc<ImportStorage>,
+    pub system_storage: Arc<SystemStorage>,
+    pub supplier_storage: Arc<SupplierStorage>,
+    pub status: Arc<Status>,
+    pub components: BTreeMap<&'static str, bool>,
+    /// Токен для изменяющих запросов и файлов поставщиков
+    pub api_token: Option<String>,
+}
+
+pub fn router(state: AppState) -> Router {
+    // Изменяющие запросы, файлы поставщиков и загрузки только с токеном API
+    let protected = Router::new()
+        .route("/files", get(files::list))
+        .route("/files/{sha256}", get(files::download))
+        .route("/files/{sha256}/reprocess", post(files::reprocess))
+        .route("/imports", get(imports::list))
+        .route("/suppliers", post(suppliers::create))
+        .route(
+            "/suppliers/{code}",
+            put(suppliers::update).delete(suppliers::delete),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_token,
+        ));
+    let api = Router::new()
+        .route("/stock", get(stock::list))
+        .route("/items", get(items::list))
+        .route("/items/{id}", get(items::get))
+        .route("/suppliers", get(suppliers::list))
+        .route("/suppliers/{code}", get(suppliers::get))
+        .merge(protected);
+    Router::new()
+        .route("/health", get(health::ready))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/metrics", get(metrics))
+        .nest("/api/v1", api)
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+async fn metrics() -> String {
+    crate::metrics::get().render()
+}
+
+/// HTTP сервер, после сигнала остановки дожидается завершения текущих запросов
+pub async fn serve(
+    addr: String,
+    state: AppState,
+    shutdown: tokio_util::sync::CancellationToken,
+) -> crate::Result<()> {
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|e| crate::AppError::Custom(format!("Не получилось занять адрес {addr}: {e}")))?;
+    tracing::info!("HTTP сервер слушает {addr}");
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+        .map_err(|e| crate::AppError::Custom(e.to_string()))
+}
@@ -0,0 +1,47 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+
+use super::AppState;
+use crate::{models::Stock, storage::StockFilter, Result};
+
+const DEFAULT_LIMIT: i32 = 50;
+const MAX_LIMIT: i32 = 500;
+
+#[derive(Deserialize)]
+pub struct StockQuery {
+    search: Option<String>,
+    supplier: Option<String>,
+    item_id: Option<uuid::Uuid>,
+    brand: Option<String>,
+    collection: Option<String>,
+    color: Option<String>,
+    width: Option<f64>,
+    article: Option<String>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<StockQuery>,
+) -> Result<Json<Vec<Stock>>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or_default().max(0);
+    // Пустые параметры вроде `?brand=` ничего не отбирают
+    let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+    let filter = StockFilter {
+        search: non_empty(query.search),
+        supplier: non_empty(query.supplier),
+        item_id: query.item_id,
+        brand: non_empty(query.brand),
+        collection: non_empty(query.collection),
+        color: non_empty(query.color),
+        width: query.width,
+        article: non_empty(query.article),
+    };
+    let result = state.stock_storage.find(&filter, limit, offset).await?;
+    Ok(Json(result))
+}
@@ -0,0 +1,101 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use super::AppState;
+use crate::{
+    models::{Supplier, SupplierInput},
+    AppError, Result,
+};
+
+/// Источники остатков, которые умеет загружать сервис
+const SOURCES: [&str; 2] = ["mail", "web"];
+
+#[derive(Deserialize)]
+pub struct NewSupplier {
+    code: String,
+    #[serde(flatten)]
+    input: SupplierInput,
+}
+
+/// Справочник поставщиков
+pub async fn list(State(state): State<AppState>) -> Result<Json<Vec<Supplier>>> {
+    let result = state.supplier_storage.list().await?;
+    Ok(Json(result))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<Json<Supplier>> {
+    let supplier = state
+        .supplier_storage
+        .get(&code)
+        .await?
+        .ok_or(not_found(&code))?;
+    Ok(Json(supplier))
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Json(body): Json<NewSupplier>,
+) -> Result<(StatusCode, Json<Supplier>)> {
+    let code = body.code.trim().to_lowercase();
+    if code.is_empty() {
+        return Err(AppError::BadRequest("Не указан код поставщика".to_string()));
+    }
+    validate(&body.input)?;
+    let supplier = state.supplier_storage.create(&code, &body.input).await?;
+    Ok((StatusCode::CREATED, Json(supplier)))
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Json(input): Json<SupplierInput>,
+) -> Result<Json<Supplier>> {
+    validate(&input)?;
+    let supplier = state
+        .supplier_storage
+        .update(&code, &input)
+        .await?
+        .ok_or(not_found(&code))?;
+    Ok(Json(supplier))
+}
+
+/// Удаляет поставщика без остатков, поставщика с остатками нужно отключить
+pub async fn delete(State(state): State<AppState>, Path(code): Path<String>) -> Result<StatusCode> {
+    if state.supplier_storage.delete(&code).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(not_found(&code))
+    }
+}
+
+fn validate(input: &SupplierInput) -> Result<()> {
+    if input.name.trim().is_empty() {
+        return Err(AppError::BadRequest(
+            "Не указано название поставщика".to_string(),
+        ));
+    }
+    if !SOURCES.contains(&input.source.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Неизвестный источник остатков {}, допустимые: {}",
+            input.source,
+            SOURCES.join(", ")
+        )));
+    }
+    if input.lead_time_days.is_some_and(|d| d < 0) {
+        return Err(AppError::BadRequest(
+            "Срок поставки не может быть отрицательным".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn not_found(code: &str) -> AppError {
+    AppError::NotFound(format!("Поставщик {code} не найден"))
+}
@@ -0,0 +1,236 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{config::SyncConfig, models::Stock, utils::matching_stock};
+
+/// Больше этого количества у поставщиков товар считается в наличии
+const IN_STOCK_THRESHOLD: f64 = 2.0;
+
+/// Значение «Наличие» для товаров, которые придут с поставщиками не дольше
+/// `max_days` рабочих дней. В настройках записывается как `3:В наличии (2-3 раб. дня)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvailabilityTier {
+    pub max_days: u32,
+    pub text: String,
+}
+
+impl FromStr for AvailabilityTier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (days, text) = s.split_once(':').ok_or(s.to_string())?;
+        let max_days = days.trim().parse().map_err(|_| s.to_string())?;
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(s.to_string());
+        }
+        Ok(AvailabilityTier {
+            max_days,
+            text: text.to_string(),
+        })
+    }
+}
+
+/// Оценка доставки товара для Мой Склад и сайта
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Estimate {
+    /// Значение «Наличие»
+    pub text: String,
+    /// Срок поставки в рабочих днях, `None` для товара под заказ
+    pub lead_days: Option<u32>,
+}
+
+/// Считает наличие по тому, у каких поставщиков есть остаток и за сколько они
+/// его привозят
+#[derive(Clone, Debug)]
+pub struct Availability {
+    /// Значения по возрастанию срока
+    tiers: Vec<AvailabilityTier>,
+    out_of_stock: String,
+    default_lead_days: u32,
+    /// Сроки поставки из справочника поставщиков
+    lead_times: HashMap<String, u32>,
+}
+
+impl Availability {
+    pub fn new(config: &SyncConfig, lead_times: HashMap<String, u32>) -> Availability {
+        let mut tiers = config.availability.clone();
+        tiers.sort_by_key(|t| t.max_days);
+        Availability {
+            tiers,
+            out_of_stock: config.out_of_stock.clone(),
+            default_lead_days: config.default_lead_days,
+            lead_times,
+        }
+    }
+    /// Все значения «Наличие», которые может выбрать синхронизация
+    pub fn texts(&self) -> Vec<&str> {
+        let mut texts = self
+            .tiers
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>();
+        texts.push(&self.out_of_stock);
+        texts
+    }
+    fn lead_days(&self, supplier: &str) -> u32 {
+        self.lead_times
+            .get(supplier)
+            .copied()
+            .unwrap_or(self.default_lead_days)
+    }
+    /// Оценка для артикула. Остаток набирается у поставщиков от самых быстрых,
+    /// пока его не станет достаточно, срок это срок последнего из них. Если
+    /// остатка не хватает или срок длиннее всех значений, товар под заказ.
+    pub fn estimate(&self, sku: &str, stock: &[Stock]) -> Estimate {
+        let mut by_supplier = HashMap::<&str, f64>::new();
+        for item in matching_stock(sku, stock) {
+            *by_supplier.entry(&item.supplier).or_default() += item.stock;
+        }
+        let mut holders = by_supplier
+            .into_iter()
+            .filter(|(_, quantity)| *quantity > 0.0)
+            .map(|(supplier, quantity)| (self.lead_days(supplier), quantity))
+            .collect::<Vec<_>>();
+        holders.sort_by_key(|(days, _)| *days);
+        let mut total = 0.0;
+        for (days, quantity) in holders {
+            total += quantity;
+            if total <= IN_STOCK_THRESHOLD {
+                continue;
+            }
+            if let Some(tier) = self.tiers.iter().find(|t| days <= t.max_days) {
+                return Estimate {
+                    text: tier.text.clone(),
+                    lead_days: Some(days),
+                };
+            }
+            break;
+        }
+        Estimate {
+            text: self.out_of_stock.clone(),
+            lead_days: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn availability() -> Availability {
+        let config = SyncConfig {
+            interval_hours: 6,
+            incoming_days: None,
+            // Порядок в настройках не важен
+            availability: vec![
+                "10:В наличии (1-2 недели)".parse().unwrap(),
+                "3:В наличии (2-3 раб. дня)".parse().unwrap(),
+            ],
+            out_of_stock: "Под заказ".to_string(),
+            default_lead_days: 3,
+        };
+        let lead_times = HashMap::from([
+            ("fast".to_string(), 1),
+            ("slow".to_string(), 7),
+            ("far".to_string(), 30),
+        ]);
+        Availability::new(&config, lead_times)
+    }
+
+    fn stock(supplier: &str, quantity: f64) -> Stock {
+        Stock {
+            supplier: supplier.to_string(),
+            name: "Ковер Шарм 2х3".to_string(),
+            article: Some("A-1".to_string()),
+            stock: quantity,
+            ..Default::default()
+        }
+    }
+
+    fn estimate(stock: &[Stock]) -> Estimate {
+        availability().estimate("A-1", stock)
+    }
+
+    fn in_stock(text: &str, days: u32) -> Estimate {
+        Estimate {
+            text: text.to_string(),
+            lead_days: Some(days),
+        }
+    }
+
+    fn out_of_stock() -> Estimate {
+        Estimate {
+            text: "Под заказ".to_string(),
+            lead_days: None,
+        }
+    }
+
+    #[test]
+    fn tier_parsed_from_config() {
+        let tier: AvailabilityTier = " 3 : В наличии (2-3 раб. дня) ".parse().unwrap();
+        assert_eq!(
+            tier,
+            AvailabilityTier {
+                max_days: 3,
+                text: "В наличии (2-3 раб. дня)".to_string(),
+            }
+        );
+        // Двоеточие в значении остается
+        let tier: AvailabilityTier = "10:Срок: 1-2 недели".parse().unwrap();
+        assert_eq!(tier.text, "Срок: 1-2 недели");
+        for wrong in ["В наличии", "три:В наличии", "-1:В наличии", "3:", "3: "]
+        {
+            assert_eq!(wrong.parse::<AvailabilityTier>(), Err(wrong.to_string()));
+        }
+    }
+
+    #[test]
+    fn each_tier_by_lead_time() {
+        let fast = in_stock("В наличии (2-3 раб. дня)", 1);
+        assert_eq!(estimate(&[stock("fast", 5.0)]), fast);
+        // Поставщик не из справочника везет за срок по умолчанию
+        let unknown = in_stock("В наличии (2-3 раб. дня)", 3);
+        assert_eq!(estimate(&[stock("other", 5.0)]), unknown);
+        let slow = in_stock("В наличии (1-2 недели)", 7);
+        assert_eq!(estimate(&[stock("slow", 5.0)]), slow);
+        // Срок длиннее всех значений
+        assert_eq!(estimate(&[stock("far", 5.0)]), out_of_stock());
+    }
+
+    #[test]
+    fn fastest_supplier_chosen() {
+        let rows = [stock("far", 50.0), stock("slow", 5.0), stock("fast", 5.0)];
+        assert_eq!(estimate(&rows), in_stock("В наличии (2-3 раб. дня)", 1));
+        // Без остатка поставщик не считается
+        let rows = [stock("fast", 0.0), stock("slow", 5.0)];
+        assert_eq!(estimate(&rows), in_stock("В наличии (1-2 недели)", 7));
+    }
+
+    #[test]
+    fn stock_accumulated_above_threshold() {
+        // Ровно порог это еще не наличие
+        assert_eq!(estimate(&[stock("fast", 2.0)]), out_of_stock());
+        assert_eq!(
+            estimate(&[stock("fast", 2.5)]),
+            in_stock("В наличии (2-3 раб. дня)", 1)
+        );
+        // Быстрому поставщику не хватает, срок по последнему из набравших остаток
+        let rows = [stock("fast", 1.5), stock("far", 10.0), stock("slow", 1.0)];
+        assert_eq!(estimate(&rows), in_stock("В наличии (1-2 недели)", 7));
+        // Строки одного поставщика складываются
+        let rows = [stock("fast", 1.5), stock("fast", 1.0)];
+        assert_eq!(estimate(&rows), in_stock("В наличии (2-3 раб. дня)", 1));
+        // Порог набирается только у поставщика со слишком долгим сроком
+        let rows = [stock("fast", 1.0), stock("far", 10.0)];
+        assert_eq!(estimate(&rows), out_of_stock());
+    }
+
+    #[test]
+    fn no_suppliers() {
+        assert_eq!(estimate(&[]), out_of_stock());
+        let mut other = stock("fast", 10.0);
+        other.article = Some("B-2".to_string());
+        other.name = "Дорожка".to_string();
+        assert_eq!(estimate(&[other]), out_of_stock());
+    }
+}
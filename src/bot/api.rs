@@ -0,0 +1,99 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{AppError, Result};
+
+const POLL_TIMEOUT: u64 = 30;
+
+#[derive(Clone)]
+pub struct BotApi {
+    client: reqwest::Client,
+    base_uri: String,
+}
+
+impl BotApi {
+    pub fn new(api_url: impl AsRef<str>, token: impl AsRef<str>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(POLL_TIMEOUT + 10))
+            .build()?;
+        let base_uri = format!(
+            "{url}/bot{token}",
+            url = api_url.as_ref().trim_end_matches('/'),
+            token = token.as_ref()
+        );
+        Ok(BotApi { client, base_uri })
+    }
+    /// Вызывает метод Bot API. Токен входит в адрес, поэтому адрес убирается из
+    /// ошибок reqwest, чтобы токен не попал в логи.
+    async fn call<P: Serialize, T: DeserializeOwned>(&self, method: &str, params: &P) -> Result<T> {
+        let uri = format!("{base}/{method}", base = self.base_uri);
+        let response: ApiResponse<T> = self
+            .client
+            .post(&uri)
+            .json(params)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?
+            .json()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+        match response.result {
+            Some(result) if response.ok => Ok(result),
+            _ => Err(AppError::Custom(format!(
+                "Ошибка вызова {method} бота: {description}",
+                description = response.description.unwrap_or_default()
+            ))),
+        }
+    }
+    pub async fn get_updates(&self, offset: i64) -> Result<Vec<Update>> {
+        let params = GetUpdates {
+            offset,
+            timeout: POLL_TIMEOUT,
+            allowed_updates: vec!["message"],
+        };
+        self.call("getUpdates", &params).await
+    }
+    pub async fn send_message(&self, chat_id: i64, text: impl Into<String>) -> Result<Message> {
+        let params = SendMessage {
+            chat_id,
+            text: text.into(),
+        };
+        self.call("sendMessage", &params).await
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    description: Option<String>,
+    result: Option<T>,
+}
+
+#[derive(Serialize)]
+struct GetUpdates {
+    offset: i64,
+    timeout: u64,
+    allowed_updates: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct SendMessage {
+    chat_id: i64,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub chat: Chat,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chat {
+    pub id: i64,
+}
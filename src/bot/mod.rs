@@ -0,0 +1,365 @@
+mod api;
+
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::{config::BotConfig, storage::StockStorage, utils::sleep_or_cancel, Result};
+use api::BotApi;
+
+/// Telegram принимает сообщения не длиннее 4096 символов
+const MAX_MESSAGE_LEN: usize = 4096;
+const SEARCH_LIMIT: i32 = 20;
+const HELP: &str = "Команды:\n/stock <название> — поиск остатков по наименованию\n/suppliers — список поставщиков и дата обновления остатков (для администраторов)\n/sync — запустить синхронизацию сайта (для администраторов)";
+
+pub struct Bot {
+    api: BotApi,
+    stock_storage: Arc<StockStorage>,
+    sync_trigger: Arc<Notify>,
+    admin_chats: Vec<i64>,
+}
+
+impl Bot {
+    pub fn new(
+        config: &BotConfig,
+        stock_storage: Arc<StockStorage>,
+        sync_trigger: Arc<Notify>,
+    ) -> Result<Arc<Self>> {
+        let api = BotApi::new(&config.api_url, &config.token)?;
+        Ok(Arc::new(Self {
+            api,
+            stock_storage,
+            sync_trigger,
+            admin_chats: config.admin_chats.clone(),
+        }))
+    }
+    pub async fn run(self: Arc<Self>, shutdown: CancellationToken) {
+        info!("Бот запущен");
+        let mut offset = 0;
+        loop {
+            let updates = tokio::select! {
+                updates = self.api.get_updates(offset) => updates,
+                _ = shutdown.cancelled() => break,
+            };
+            let updates = match updates {
+                Ok(updates) => updates,
+                Err(e) => {
+                    error!("Ошибка получения обновлений бота: {e:?}");
+                    if sleep_or_cancel(10, &shutdown).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            for update in updates {
+                offset = offset.max(update.update_id + 1);
+                let Some(message) = update.message else {
+                    continue;
+                };
+                let Some(text) = message.text else {
+                    continue;
+                };
+                let chat_id = message.chat.id;
+                let is_admin = self.admin_chats.contains(&chat_id);
+                let reply = self.handle(&text, is_admin).await;
+                for chunk in split_message(&reply) {
+                    if let Err(e) = self.api.send_message(chat_id, chunk).await {
+                        error!("Ошибка отправки сообщения в чат {chat_id}: {e:?}");
+                    }
+                }
+            }
+        }
+        info!("Бот остановлен");
+    }
+    async fn handle(&self, text: &str, is_admin: bool) -> String {
+        let (command, args) = parse_command(text);
+        let result = match command.as_str() {
+            "/stock" | "/search" => self.stock(args, is_admin).await,
+            "/suppliers" if is_admin => self.suppliers().await,
+            "/sync" if is_admin => Ok(self.sync()),
+            _ => Ok(HELP.to_string()),
+        };
+        result.unwrap_or_else(|e| {
+            error!("Ошибка обработки команды {command}: {e:?}");
+            "⚠️ Ошибка при обработке команды, попробуйте позже".to_string()
+        })
+    }
+    async fn stock(&self, query: &str, is_admin: bool) -> Result<String> {
+        if query.is_empty() {
+            return Ok("Укажите наименование: /stock <название>".to_string());
+        }
+        let items = self.stock_storage.search(query, SEARCH_LIMIT, 0).await?;
+        if items.is_empty() {
+            return Ok(format!("Ничего не нашла по запросу «{query}»"));
+        }
+        // поставщика показываем только администраторам
+        let result = items
+            .iter()
+            .map(|s| {
+                if is_admin {
+                    s.to_string()
+                } else {
+                    s.safe_print()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok(result)
+    }
+    async fn suppliers(&self) -> Result<String> {
+        let suppliers = self.stock_storage.suppliers().await?;
+        if suppliers.is_empty() {
+            return Ok("Остатков поставщиков пока нет".to_string());
+        }
+        let result = suppliers
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok(result)
+    }
+    fn sync(&self) -> String {
+        self.sync_trigger.notify_one();
+        "🔄 Синхронизация запущена".to_string()
+    }
+}
+
+fn parse_command(text: &str) -> (String, &str) {
+    let text = text.trim();
+    let (command, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let command = command.split('@').next().unwrap_or_default().to_lowercase();
+    (command, args.trim())
+}
+
+/// Делит ответ на сообщения не длиннее `MAX_MESSAGE_LEN` символов по абзацам.
+/// Слишком длинный абзац делится по строкам, слишком длинная строка по символам.
+fn split_message(text: &str) -> Vec<String> {
+    let mut messages = Messages::default();
+    for block in text.split("\n\n") {
+        if length(block) <= MAX_MESSAGE_LEN {
+            messages.push("\n\n", block);
+            continue;
+        }
+        for (i, line) in block.split('\n').enumerate() {
+            let separator = if i == 0 { "\n\n" } else { "\n" };
+            if length(line) <= MAX_MESSAGE_LEN {
+                messages.push(separator, line);
+                continue;
+            }
+            let chars = line.chars().collect::<Vec<_>>();
+            for (j, chunk) in chars.chunks(MAX_MESSAGE_LEN).enumerate() {
+                let separator = if j == 0 { separator } else { "" };
+                messages.push(separator, &chunk.iter().collect::<String>());
+            }
+        }
+    }
+    messages.finish()
+}
+
+fn length(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Готовые сообщения и сообщение, которое еще собирается
+#[derive(Default)]
+struct Messages {
+    done: Vec<String>,
+    current: String,
+}
+
+impl Messages {
+    /// Дописывает кусок через разделитель или, если он не помещается, начинает
+    /// с него новое сообщение
+    fn push(&mut self, separator: &str, piece: &str) {
+        if !self.current.is_empty()
+            && length(&self.current) + length(separator) + length(piece) > MAX_MESSAGE_LEN
+        {
+            self.done.push(std::mem::take(&mut self.current));
+        }
+        if !self.current.is_empty() {
+            self.current.push_str(separator);
+        }
+        self.current.push_str(piece);
+    }
+    fn finish(mut self) -> Vec<String> {
+        if !self.current.is_empty() {
+            self.done.push(self.current);
+        }
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    };
+
+    use axum::{extract::State, routing::post, Json, Router};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            parse_command("/stock ковер беж"),
+            ("/stock".into(), "ковер беж")
+        );
+        assert_eq!(
+            parse_command("  /Stock@friday_bot   ковер  "),
+            ("/stock".into(), "ковер")
+        );
+        assert_eq!(parse_command("/suppliers"), ("/suppliers".into(), ""));
+        assert_eq!(parse_command("/sync\nсейчас"), ("/sync".into(), "сейчас"));
+        assert_eq!(parse_command(""), ("".into(), ""));
+    }
+
+    #[test]
+    fn split_by_blocks() {
+        assert!(split_message("").is_empty());
+        assert_eq!(split_message("один\n\nдва"), ["один\n\nдва"]);
+        let block = "б".repeat(3000);
+        let text = format!("{block}\n\n{block}\n\n{block}");
+        assert_eq!(split_message(&text), [block.clone(), block.clone(), block]);
+    }
+
+    #[test]
+    fn split_counts_chars_not_bytes() {
+        // 4096 кириллических символов это 8192 байта, но одно сообщение
+        let text = "я".repeat(MAX_MESSAGE_LEN);
+        assert_eq!(split_message(&text), vec![text]);
+    }
+
+    #[test]
+    fn split_oversized_block_by_lines() {
+        let line = "с".repeat(3000);
+        let block = format!("{line}\n{line}");
+        let text = format!("начало\n\n{block}");
+        assert_eq!(
+            split_message(&text),
+            [format!("начало\n\n{line}"), line.clone()]
+        );
+    }
+
+    #[test]
+    fn split_oversized_line_by_chars() {
+        let text = "ж".repeat(MAX_MESSAGE_LEN * 2 + 10);
+        let messages = split_message(&text);
+        let lengths = messages.iter().map(|m| length(m)).collect::<Vec<_>>();
+        assert_eq!(lengths, [MAX_MESSAGE_LEN, MAX_MESSAGE_LEN, 10]);
+        assert_eq!(messages.concat(), text);
+    }
+
+    /// Сервер Bot API: отдает заданные обновления и запоминает запросы
+    #[derive(Clone, Default)]
+    struct MockApi {
+        updates: Arc<Mutex<Vec<Value>>>,
+        offsets: Arc<Mutex<Vec<i64>>>,
+        sent: Arc<Mutex<Vec<Value>>>,
+    }
+
+    async fn get_updates(State(mock): State<MockApi>, Json(params): Json<Value>) -> Json<Value> {
+        mock.offsets
+            .lock()
+            .unwrap()
+            .push(params["offset"].as_i64().unwrap());
+        let updates = std::mem::take(&mut *mock.updates.lock().unwrap());
+        if updates.is_empty() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        Json(json!({ "ok": true, "result": updates }))
+    }
+
+    async fn send_message(State(mock): State<MockApi>, Json(params): Json<Value>) -> Json<Value> {
+        mock.sent.lock().unwrap().push(params.clone());
+        let message = json!({ "chat": { "id": params["chat_id"] }, "text": params["text"] });
+        Json(json!({ "ok": true, "result": message }))
+    }
+
+    async fn unauthorized() -> Json<Value> {
+        Json(json!({ "ok": false, "error_code": 401, "description": "Unauthorized" }))
+    }
+
+    /// Запускает сервер на свободном порту и возвращает его адрес для `BOT_API_URL`
+    async fn serve(mock: MockApi) -> String {
+        let app = Router::new()
+            .route("/bottest/getUpdates", post(get_updates))
+            .route("/bottest/sendMessage", post(send_message))
+            .route("/botwrong/getUpdates", post(unauthorized))
+            .with_state(mock);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}/")
+    }
+
+    fn update(id: i64, chat: i64, text: &str) -> Value {
+        json!({ "update_id": id, "message": { "chat": { "id": chat }, "text": text } })
+    }
+
+    #[tokio::test]
+    async fn replies_to_commands() {
+        let mock = MockApi::default();
+        *mock.updates.lock().unwrap() = vec![
+            update(7, 1, "/sync@friday_bot"),
+            update(8, 2, "/sync"),
+            json!({ "update_id": 9 }),
+        ];
+        let config = BotConfig {
+            token: "test".to_string(),
+            api_url: serve(mock.clone()).await,
+            admin_chats: vec![1],
+        };
+        // Команды без поиска не обращаются к базе
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let sync_trigger = Arc::new(Notify::new());
+        let bot = Bot::new(
+            &config,
+            Arc::new(StockStorage::new(pool)),
+            sync_trigger.clone(),
+        )
+        .unwrap();
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn(bot.run(shutdown.clone()));
+        let started = Instant::now();
+        while mock.sent.lock().unwrap().len() < 2 || mock.offsets.lock().unwrap().len() < 2 {
+            assert!(started.elapsed() < Duration::from_secs(5), "Бот не ответил");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.cancel();
+        task.await.unwrap();
+
+        let sent = mock.sent.lock().unwrap().clone();
+        assert_eq!(
+            sent[0],
+            json!({ "chat_id": 1, "text": "🔄 Синхронизация запущена" })
+        );
+        // Синхронизацию запускают только администраторы, остальным справка
+        assert_eq!(sent[1], json!({ "chat_id": 2, "text": HELP }));
+        tokio::time::timeout(Duration::from_secs(1), sync_trigger.notified())
+            .await
+            .expect("Синхронизация не запущена");
+        let offsets = mock.offsets.lock().unwrap().clone();
+        assert_eq!(offsets[..2], [0, 10]);
+    }
+
+    #[tokio::test]
+    async fn transport_error_hides_token() {
+        // На этом порту никто не слушает
+        let api = BotApi::new("http://127.0.0.1:1", "123:secret").unwrap();
+        let error = api.send_message(1, "текст").await.unwrap_err();
+        assert!(!format!("{error:?}").contains("secret"), "{error:?}");
+        assert!(!error.to_string().contains("secret"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn api_error_description() {
+        let api = BotApi::new(serve(MockApi::default()).await, "wrong").unwrap();
+        let error = api.get_updates(0).await.unwrap_err();
+        assert!(format!("{error}").contains("Unauthorized"), "{error}");
+    }
+}
@@ -0,0 +1,362 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{anyhow, bail};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    health::Status,
+    models::Stock,
+    stock_service::{self, FetchMap, SourceFile},
+    storage::{StockStorage, SupplierStorage},
+    synchronizer::Synchronizer,
+    Config, LocalService,
+};
+
+pub const USAGE: &str = "\
+Использование: friday-api [КОМАНДА]
+
+Команды:
+  serve                          запустить сервис (по умолчанию)
+  parse <поставщик> <файл> [--json]
+                                 разобрать файл парсером поставщика и вывести остатки
+  fetch-mail [--json]            получить почту один раз и вывести разобранные остатки
+  scrape [--json]                скачать остатки из сети один раз и вывести их
+  sync [--dry-run]               синхронизировать Мой Склад и сайт один раз
+  migrate                        применить миграции базы данных
+  freshness                      показать, когда обновлялись остатки поставщиков
+  help                           показать эту справку
+
+Ничего не сохраняется, кроме команд sync без --dry-run и migrate.";
+
+/// Команда командной строки
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Serve,
+    Parse {
+        supplier: String,
+        path: PathBuf,
+        json: bool,
+    },
+    FetchMail {
+        json: bool,
+    },
+    Scrape {
+        json: bool,
+    },
+    Sync {
+        dry_run: bool,
+    },
+    Migrate,
+    Freshness,
+    Help,
+}
+
+impl Command {
+    /// Разбирает аргументы без имени программы
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Command> {
+        let mut args = args.into_iter();
+        let Some(name) = args.next() else {
+            return Ok(Command::Serve);
+        };
+        let mut positional = Vec::new();
+        let mut json = false;
+        let mut dry_run = false;
+        for arg in args {
+            match arg.as_str() {
+                "--json" => json = true,
+                "--dry-run" => dry_run = true,
+                flag if flag.starts_with("--") => bail!("Неизвестный флаг {flag}"),
+                _ => positional.push(arg),
+            }
+        }
+        let command = match name.as_str() {
+            "serve" => Command::Serve,
+            "parse" => {
+                let [supplier, path] = <[String; 2]>::try_from(positional)
+                    .map_err(|_| anyhow!("parse ждет поставщика и путь к файлу"))?;
+                return Ok(Command::Parse {
+                    supplier,
+                    path: PathBuf::from(path),
+                    json,
+                });
+            }
+            "fetch-mail" => Command::FetchMail { json },
+            "scrape" => Command::Scrape { json },
+            "sync" => Command::Sync { dry_run },
+            "migrate" => Command::Migrate,
+            "freshness" => Command::Freshness,
+            "help" | "--help" | "-h" => Command::Help,
+            other => bail!("Неизвестная команда {other}"),
+        };
+        if !positional.is_empty() {
+            bail!("Лишние аргументы: {}", positional.join(" "));
+        }
+        Ok(command)
+    }
+}
+
+pub async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Serve => serve().await,
+        Command::Parse {
+            supplier,
+            path,
+            json,
+        } => parse(supplier, path, json).await,
+        Command::FetchMail { json } => {
+            let config = Config::load()?;
+            let mail = config.mail.as_ref().ok_or(anyhow!("Почта не настроена"))?;
+            let pool = connect(&config).await?;
+            let senders = SupplierStorage::new(pool).senders().await?;
+            let fetches = stock_service::fetch_mail(mail, senders).await?;
+            print_fetches(fetches, json).await
+        }
+        Command::Scrape { json } => {
+            let config = Config::load()?;
+            let mut fetches = FetchMap::new();
+            let mut failed = Vec::new();
+            for (supplier, result) in stock_service::scrape(&config).await? {
+                match result {
+                    Ok(fetched) => fetches.extend(fetched),
+                    Err(e) => {
+                        eprintln!("{supplier}: {e}");
+                        failed.push(supplier);
+                    }
+                }
+            }
+            print_fetches(fetches, json).await?;
+            if !failed.is_empty() {
+                bail!("Не получилось скачать остатки: {}", failed.join(", "));
+            }
+            Ok(())
+        }
+        Command::Sync { dry_run } => sync(dry_run).await,
+        Command::Migrate => {
+            let config = Config::load()?;
+            let pool = connect(&config).await?;
+            LocalService::new(pool, config).migrate().await?;
+            println!("Миграции применены");
+            Ok(())
+        }
+        Command::Freshness => freshness().await,
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+async fn connect(config: &Config) -> anyhow::Result<sqlx::PgPool> {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&config.database_url)
+        .await?;
+    Ok(pool)
+}
+
+async fn serve() -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let pool = connect(&config).await?;
+    let shutdown = CancellationToken::new();
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        crate::utils::shutdown_signal().await;
+        signal_shutdown.cancel();
+    });
+    let service = LocalService::new(pool, config);
+    service.run(shutdown).await;
+    Ok(())
+}
+
+async fn parse(supplier: String, path: PathBuf, json: bool) -> anyhow::Result<()> {
+    let content = std::fs::read(&path)
+        .map_err(|e| anyhow!("Не получилось прочитать {}: {e}", path.display()))?;
+    let file = SourceFile {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        content,
+    };
+    let parsed = stock_service::parse_files(&supplier, vec![file], chrono::Utc::now()).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&parsed)?);
+    } else {
+        print_items(&parsed.items);
+        println!("{}", parsed.report);
+    }
+    Ok(())
+}
+
+async fn print_fetches(fetches: FetchMap, json: bool) -> anyhow::Result<()> {
+    let mut all = BTreeMap::new();
+    for (supplier, (files, received)) in fetches {
+        if !json {
+            println!(
+                "{supplier}: файлов {}, получено {}",
+                files.len(),
+                received.format("%d.%m.%Y %H:%M")
+            );
+            for file in &files {
+                println!("  {} ({} байт)", file.name, file.content.len());
+            }
+        }
+        match stock_service::parse_files(&supplier, files, received).await {
+            Ok(parsed) => {
+                if !json {
+                    println!("  строк остатков: {}", parsed.items.len());
+                    println!("{}", parsed.report);
+                }
+                all.insert(supplier, parsed);
+            }
+            Err(e) => eprintln!("{supplier}: {e}"),
+        }
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&all)?);
+    }
+    Ok(())
+}
+
+fn print_items(items: &[Stock]) {
+    for item in items {
+        println!("{item}\n");
+    }
+}
+
+async fn sync(dry_run: bool) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let ms_config = config
+        .moysklad
+        .as_ref()
+        .ok_or(anyhow!("Мой Склад не настроен"))?;
+    let ms_client = std::sync::Arc::new(
+        rust_moysklad::MoySkladApiClient::new(ms_config.token.clone())
+            .map_err(|e| anyhow!("Не получилось создать клиент Мой Склад: {e:?}"))?,
+    );
+    let safira_client = match &config.safira {
+        Some(safira) => Some(std::sync::Arc::new(
+            rust_woocommerce::ApiClient::init(
+                safira.host.clone(),
+                safira.ck.clone(),
+                safira.cs.clone(),
+            )
+            .map_err(|e| anyhow!("Не получилось создать клиент WooCommerce: {e:?}"))?,
+        )),
+        None => None,
+    };
+    let pool = connect(&config).await?;
+    let syncer = Synchronizer::new(
+        ms_client,
+        safira_client,
+        std::sync::Arc::new(StockStorage::new(pool.clone())),
+        std::sync::Arc::new(SupplierStorage::new(pool)),
+        std::sync::Arc::new(tokio::sync::Notify::new()),
+        &config.sync,
+        Status::new(),
+        CancellationToken::new(),
+    );
+    syncer.sync_once(dry_run).await?;
+    println!("Синхронизация завершена");
+    Ok(())
+}
+
+async fn freshness() -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let pool = connect(&config).await?;
+    let suppliers = StockStorage::new(pool).suppliers().await?;
+    let now = chrono::Utc::now();
+    println!(
+        "{:<15} {:>8}  {:<16}  {:>6}",
+        "Поставщик", "Позиций", "Обновлено", "Часов"
+    );
+    for summary in suppliers {
+        println!(
+            "{:<15} {:>8}  {:<16}  {:>6}",
+            summary.supplier,
+            summary.items,
+            summary.updated.format("%d.%m.%Y %H:%M"),
+            (now - summary.updated).num_hours()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> anyhow::Result<Command> {
+        Command::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    fn error(args: &[&str]) -> String {
+        parse(args).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn commands_parsed() {
+        assert_eq!(parse(&[]).unwrap(), Command::Serve);
+        assert_eq!(parse(&["serve"]).unwrap(), Command::Serve);
+        assert_eq!(
+            parse(&["parse", "fenix", "stock.xlsx", "--json"]).unwrap(),
+            Command::Parse {
+                supplier: "fenix".to_string(),
+                path: PathBuf::from("stock.xlsx"),
+                json: true,
+            }
+        );
+        // Флаги можно писать перед аргументами
+        assert_eq!(
+            parse(&["parse", "--json", "fenix", "stock.xlsx"]).unwrap(),
+            parse(&["parse", "fenix", "stock.xlsx", "--json"]).unwrap()
+        );
+        assert_eq!(
+            parse(&["fetch-mail"]).unwrap(),
+            Command::FetchMail { json: false }
+        );
+        assert_eq!(
+            parse(&["scrape", "--json"]).unwrap(),
+            Command::Scrape { json: true }
+        );
+        assert_eq!(
+            parse(&["sync", "--dry-run"]).unwrap(),
+            Command::Sync { dry_run: true }
+        );
+        assert_eq!(parse(&["migrate"]).unwrap(), Command::Migrate);
+        assert_eq!(parse(&["freshness"]).unwrap(), Command::Freshness);
+    }
+
+    #[test]
+    fn help_aliases() {
+        for alias in ["help", "--help", "-h"] {
+            assert_eq!(parse(&[alias]).unwrap(), Command::Help);
+        }
+    }
+
+    #[test]
+    fn unknown_command_and_flag() {
+        assert_eq!(error(&["import"]), "Неизвестная команда import");
+        assert_eq!(error(&["sync", "--force"]), "Неизвестный флаг --force");
+        // Флаг проверяется раньше команды
+        assert_eq!(error(&["import", "--force"]), "Неизвестный флаг --force");
+    }
+
+    #[test]
+    fn parse_needs_supplier_and_path() {
+        let message = "parse ждет поставщика и путь к файлу";
+        assert_eq!(error(&["parse"]), message);
+        assert_eq!(error(&["parse", "fenix"]), message);
+        assert_eq!(error(&["parse", "fenix", "a.xlsx", "b.xlsx"]), message);
+    }
+
+    #[test]
+    fn extra_arguments_rejected() {
+        assert_eq!(error(&["migrate", "now"]), "Лишние аргументы: now");
+        assert_eq!(
+            error(&["sync", "--dry-run", "all", "suppliers"]),
+            "Лишние аргументы: all suppliers"
+        );
+        assert_eq!(error(&["help", "sync"]), "Лишние аргументы: sync");
+    }
+}
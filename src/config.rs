@@ -0,0 +1,515 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{availability::AvailabilityTier, AppError, Result};
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const DEFAULT_PORT: u16 = 8000;
+const DEFAULT_SYNC_INTERVAL_HOURS: u64 = 6;
+const DEFAULT_WEB_INTERVAL_HOURS: u64 = 24;
+/// Источники остатков в сети, у каждого свое расписание `<ИСТОЧНИК>_INTERVAL_HOURS`
+const WEB_SOURCES: [&str; 3] = ["ortgraph", "vvk", "sportflooring"];
+const DEFAULT_IN_STOCK: &str = "В наличии (2-3 раб. дня)";
+const DEFAULT_OUT_OF_STOCK: &str = "Под заказ (5-8 недель)";
+const DEFAULT_LEAD_DAYS: u32 = 3;
+pub const DEFAULT_BOT_API_URL: &str = "https://api.telegram.org";
+
+/// Настройки сервиса. Читаются из переменных окружения и необязательного TOML файла
+/// (`CONFIG_FILE`, по умолчанию `config.toml`). Секции файла превращаются в имена
+/// переменных: `[mail] host = "..."` это `MAIL_HOST`. Переменные окружения важнее файла.
+///
+/// Каждая интеграция необязательна: она включается, если заданы ее параметры, и
+/// выключается явно через `<СЕКЦИЯ>_ENABLED=false`. Частично заданная секция это ошибка.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub database_url: String,
+    pub port: u16,
+    /// Токен для изменяющих запросов API, файлов поставщиков и загрузок
+    /// (`Authorization: Bearer ...`). Без него эти запросы отключены
+    pub api_token: Option<String>,
+    pub mail: Option<MailConfig>,
+    pub ortgraph: Option<OrtgraphConfig>,
+    pub vvk_enabled: bool,
+    pub sportflooring_enabled: bool,
+    /// Через сколько часов после удачной загрузки снова скачивать остатки источника в сети
+    pub web_intervals: HashMap<String, u64>,
+    pub moysklad: Option<MoySkladConfig>,
+    pub safira: Option<WooConfig>,
+    pub sync: SyncConfig,
+    pub bot: Option<BotConfig>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MailConfig {
+    pub host: String,
+    pub user: String,
+    pub pass: String,
+    /// Ждать новые письма через IMAP IDLE, а не только опрашивать ящик раз в час
+    pub idle: bool,
+    /// Что делать с письмами после загрузки, если для поставщика не задано свое
+    pub actions: MailActions,
+    /// Свои действия поставщиков: `[mail.fox] processed_folder = "..."`
+    pub supplier_actions: HashMap<String, MailActions>,
+    /// Поставщики, которые делят остатки на несколько писем
+    pub split_suppliers: Vec<String>,
+    /// Письма такого поставщика, пришедшие не раньше этого срока до самого нового,
+    /// объединяются в одну загрузку
+    pub split_window: chrono::Duration,
+}
+
+/// Действия с письмами поставщика после загрузки остатков
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MailActions {
+    /// Флаг или ключевое слово IMAP, например `\Seen` или `Imported`
+    pub flag: Option<String>,
+    /// Папка для писем, остатки из которых сохранены
+    pub processed_folder: Option<String>,
+    /// Папка для писем, из которых не получилось прочитать остатки
+    pub errors_folder: Option<String>,
+}
+
+impl MailActions {
+    const KEYS: [&'static str; 3] = ["FLAG", "PROCESSED_FOLDER", "ERRORS_FOLDER"];
+
+    fn set(&mut self, key: &str, value: String) {
+        match key {
+            "FLAG" => self.flag = Some(value),
+            "PROCESSED_FOLDER" => self.processed_folder = Some(value),
+            "ERRORS_FOLDER" => self.errors_folder = Some(value),
+            _ => {}
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self == &MailActions::default()
+    }
+}
+
+impl MailConfig {
+    /// Действия для писем поставщика: свои поверх общих
+    pub fn actions(&self, supplier: &str) -> MailActions {
+        let mut actions = self.actions.clone();
+        if let Some(own) = self.supplier_actions.get(supplier) {
+            actions.flag = own.flag.clone().or(actions.flag);
+            actions.processed_folder = own.processed_folder.clone().or(actions.processed_folder);
+            actions.errors_folder = own.errors_folder.clone().or(actions.errors_folder);
+        }
+        actions
+    }
+    /// Окно, в котором объединяются письма поставщика, или `None`, если
+    /// применяется только самое новое письмо
+    pub fn split_window(&self, supplier: &str) -> Option<chrono::Duration> {
+        self.split_suppliers
+            .iter()
+            .any(|s| s == supplier)
+            .then_some(self.split_window)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OrtgraphConfig {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct MoySkladConfig {
+    pub token: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct WooConfig {
+    pub host: String,
+    pub ck: String,
+    pub cs: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct SyncConfig {
+    pub interval_hours: u64,
+    /// Приход, который ожидается не позже чем через столько дней, считается
+    /// наличием. Без настройки учитывается только свободный остаток.
+    pub incoming_days: Option<u64>,
+    /// Значения «Наличие» в Мой Склад по сроку поставки
+    pub availability: Vec<AvailabilityTier>,
+    /// Значение «Наличие» для товаров, которых нет у поставщиков
+    pub out_of_stock: String,
+    /// Срок поставки для поставщиков, у которых он не указан в справочнике
+    pub default_lead_days: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct BotConfig {
+    pub token: String,
+    pub api_url: String,
+    pub admin_chats: Vec<i64>,
+}
+
+impl Config {
+    /// Загружает настройки и проверяет их. Все отсутствующие и некорректные
+    /// параметры перечисляются в одной ошибке.
+    pub fn load() -> Result<Config> {
+        let mut values = HashMap::new();
+        let explicit_file = std::env::var("CONFIG_FILE").ok();
+        let path = explicit_file
+            .clone()
+            .unwrap_or(DEFAULT_CONFIG_FILE.to_string());
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                let table = content.parse::<toml::Table>().map_err(|e| {
+                    AppError::ConfigError(format!("Ошибка чтения файла настроек {path}: {e}"))
+                })?;
+                flatten("", &table, &mut values);
+            }
+            Err(e) if explicit_file.is_some() => {
+                return Err(AppError::ConfigError(format!(
+                    "Не получилось открыть файл настроек {path}: {e}"
+                )));
+            }
+            Err(_) => {}
+        }
+        Config::from_values(merge(values, std::env::vars()))
+    }
+    fn from_values(values: HashMap<String, String>) -> Result<Config> {
+        let mut source = Source::new(values);
+        let config = Config {
+            database_url: source.required("DATABASE_URL"),
+            port: source.parsed("PORT").unwrap_or(DEFAULT_PORT),
+            api_token: source.optional("API_TOKEN"),
+            mail: source
+                .section("MAIL", &["MAIL_HOST", "MAIL_USER", "MAIL_PASS"])
+                .map(|mut v| MailConfig {
+                    host: v.remove("MAIL_HOST").unwrap_or_default(),
+                    user: v.remove("MAIL_USER").unwrap_or_default(),
+                    pass: v.remove("MAIL_PASS").unwrap_or_default(),
+                    idle: source.parsed("MAIL_IDLE").unwrap_or(true),
+                    actions: source.mail_actions(),
+                    supplier_actions: source.supplier_mail_actions(),
+                    split_suppliers: source.list("MAIL_SPLIT"),
+                    split_window: chrono::Duration::minutes(
+                        source.parsed("MAIL_SPLIT_WINDOW").unwrap_or(60),
+                    ),
+                }),
+            ortgraph: source
+                .section("ORTGRAPH", &["ORTGRAPH_USERNAME", "ORTGRAPH_PASSWORD"])
+                .map(|mut v| OrtgraphConfig {
+                    username: v.remove("ORTGRAPH_USERNAME").unwrap_or_default(),
+                    password: v.remove("ORTGRAPH_PASSWORD").unwrap_or_default(),
+                }),
+            vvk_enabled: source.parsed("VVK_ENABLED").unwrap_or(true),
+            sportflooring_enabled: source.parsed("SPORTFLOORING_ENABLED").unwrap_or(true),
+            web_intervals: WEB_SOURCES
+                .iter()
+                .filter_map(|code| {
+                    let key = format!("{}_INTERVAL_HOURS", code.to_uppercase());
+                    Some((code.to_string(), source.hours(&key)?))
+                })
+                .collect(),
+            moysklad: source
+                .section("MS", &["MS_TOKEN"])
+                .map(|mut v| MoySkladConfig {
+                    token: v.remove("MS_TOKEN").unwrap_or_default(),
+                }),
+            safira: source
+                .section("SAFIRA", &["SAFIRA_HOST", "SAFIRA_CK", "SAFIRA_CS"])
+                .map(|mut v| WooConfig {
+                    host: v.remove("SAFIRA_HOST").unwrap_or_default(),
+                    ck: v.remove("SAFIRA_CK").unwrap_or_default(),
+                    cs: v.remove("SAFIRA_CS").unwrap_or_default(),
+                }),
+            sync: SyncConfig {
+                interval_hours: source
+                    .hours("SYNC_INTERVAL_HOURS")
+                    .unwrap_or(DEFAULT_SYNC_INTERVAL_HOURS),
+                incoming_days: source.parsed("SYNC_INCOMING_DAYS"),
+                availability: source.availability(),
+                out_of_stock: source
+                    .optional("SYNC_OUT_OF_STOCK")
+                    .unwrap_or(DEFAULT_OUT_OF_STOCK.to_string()),
+                default_lead_days: source
+                    .parsed("SYNC_DEFAULT_LEAD_DAYS")
+                    .unwrap_or(DEFAULT_LEAD_DAYS),
+            },
+            bot: source.optional("BOT_TOKEN").map(|token| BotConfig {
+                token,
+                api_url: source
+                    .optional("BOT_API_URL")
+                    .unwrap_or(DEFAULT_BOT_API_URL.to_string()),
+                admin_chats: source.list("BOT_ADMIN_CHATS"),
+            }),
+        };
+        source.finish()?;
+        Ok(config)
+    }
+    /// Через сколько часов снова скачивать остатки источника в сети
+    pub fn web_interval(&self, supplier: &str) -> u64 {
+        self.web_intervals
+            .get(supplier)
+            .copied()
+            .unwrap_or(DEFAULT_WEB_INTERVAL_HOURS)
+    }
+    /// Какие интеграции включены, для логов и `/health`
+    pub fn components(&self) -> BTreeMap<&'static str, bool> {
+        BTreeMap::from([
+            ("imap", self.mail.is_some()),
+            ("ortgraph", self.ortgraph.is_some()),
+            ("vvk", self.vvk_enabled),
+            ("sportflooring", self.sportflooring_enabled),
+            ("moysklad", self.moysklad.is_some()),
+            ("woocommerce", self.safira.is_some()),
+            ("bot", self.bot.is_some()),
+        ])
+    }
+}
+
+struct Source {
+    values: HashMap<String, String>,
+    missing: Vec<String>,
+    invalid: Vec<String>,
+}
+
+impl Source {
+    fn new(values: HashMap<String, String>) -> Self {
+        Self {
+            values,
+            missing: Vec::new(),
+            invalid: Vec::new(),
+        }
+    }
+    fn optional(&self, key: &str) -> Option<String> {
+        self.values
+            .get(key)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+    /// Секция включена, если задан хотя бы один ее параметр и она не выключена через
+    /// `<PREFIX>_ENABLED=false`. Тогда обязательны все параметры секции.
+    fn section(&mut self, prefix: &str, keys: &[&str]) -> Option<HashMap<String, String>> {
+        let enabled_key = format!("{prefix}_ENABLED");
+        if !self.parsed::<bool>(&enabled_key).unwrap_or(true) {
+            return None;
+        }
+        if keys.iter().all(|key| self.optional(key).is_none()) {
+            return None;
+        }
+        let values = keys
+            .iter()
+            .map(|key| (key.to_string(), self.required(key)))
+            .collect();
+        Some(values)
+    }
+    fn required(&mut self, key: &str) -> String {
+        self.optional(key).unwrap_or_else(|| {
+            self.missing.push(key.to_string());
+            String::new()
+        })
+    }
+    fn parsed<T: std::str::FromStr>(&mut self, key: &str) -> Option<T> {
+        let value = self.optional(key)?;
+        value
+            .parse()
+            .inspect_err(|_| self.invalid.push(format!("{key}={value}")))
+            .ok()
+    }
+    /// Интервал в часах: с нулем задача повторялась бы без паузы
+    fn hours(&mut self, key: &str) -> Option<u64> {
+        let hours = self.parsed(key)?;
+        if hours == 0 {
+            self.invalid.push(format!("{key}=0"));
+            return None;
+        }
+        Some(hours)
+    }
+    fn list<T: std::str::FromStr>(&mut self, key: &str) -> Vec<T> {
+        let mut result = Vec::new();
+        for item in self.optional(key).unwrap_or_default().split(',') {
+            let item = item.trim();
+            if item.is_empty() {
+                continue;
+            }
+            match item.parse() {
+                Ok(v) => result.push(v),
+                Err(_) => self.invalid.push(format!("{key}={item}")),
+            }
+        }
+        result
+    }
+    /// Значения «Наличие» `SYNC_AVAILABILITY`, без настройки одно значение для
+    /// поставки за `DEFAULT_LEAD_DAYS` дней
+    fn availability(&mut self) -> Vec<AvailabilityTier> {
+        let tiers = self.list("SYNC_AVAILABILITY");
+        if tiers.is_empty() {
+            vec![AvailabilityTier {
+                max_days: DEFAULT_LEAD_DAYS,
+                text: DEFAULT_IN_STOCK.to_string(),
+            }]
+        } else {
+            tiers
+        }
+    }
+    /// Общие действия с письмами: `MAIL_FLAG`, `MAIL_PROCESSED_FOLDER`, `MAIL_ERRORS_FOLDER`
+    fn mail_actions(&self) -> MailActions {
+        let mut actions = MailActions::default();
+        for key in MailActions::KEYS {
+            if let Some(value) = self.optional(&format!("MAIL_{key}")) {
+                actions.set(key, value);
+            }
+        }
+        actions
+    }
+    /// Действия поставщиков: `MAIL_<ПОСТАВЩИК>_FLAG` и так далее
+    fn supplier_mail_actions(&self) -> HashMap<String, MailActions> {
+        let mut result = HashMap::<String, MailActions>::new();
+        for key in self.values.keys() {
+            let Some(rest) = key.strip_prefix("MAIL_") else {
+                continue;
+            };
+            for action in MailActions::KEYS {
+                let Some(supplier) = rest.strip_suffix(&format!("_{action}")) else {
+                    continue;
+                };
+                if let Some(value) = self.optional(key) {
+                    result
+                        .entry(supplier.to_lowercase())
+                        .or_default()
+                        .set(action, value);
+                }
+            }
+        }
+        result
+    }
+    fn finish(self) -> Result<()> {
+        let mut problems = Vec::new();
+        if !self.missing.is_empty() {
+            problems.push(format!("не заданы: {}", self.missing.join(", ")));
+        }
+        if !self.invalid.is_empty() {
+            problems.push(format!(
+                "некорректные значения: {}",
+                self.invalid.join(", ")
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ConfigError(format!(
+                "Ошибка настроек, {}",
+                problems.join("; ")
+            )))
+        }
+    }
+}
+
+/// Значения файла, поверх которых переменные окружения. Пустые переменные не
+/// считаются: compose передает `VAR=${VAR}`, даже если переменная не задана.
+fn merge(
+    mut values: HashMap<String, String>,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> HashMap<String, String> {
+    values.extend(env.into_iter().filter(|(_, v)| !v.trim().is_empty()));
+    values
+}
+
+fn flatten(prefix: &str, table: &toml::Table, values: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.to_uppercase()
+        } else {
+            format!("{prefix}_{}", key.to_uppercase())
+        };
+        match value {
+            toml::Value::Table(t) => flatten(&key, t, values),
+            toml::Value::String(s) => {
+                values.insert(key, s.clone());
+            }
+            toml::Value::Array(a) => {
+                let joined = a
+                    .iter()
+                    .map(|v| match v {
+                        toml::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                values.insert(key, joined);
+            }
+            other => {
+                values.insert(key, other.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(file: &str, env: &[(&str, &str)]) -> HashMap<String, String> {
+        let mut values = HashMap::new();
+        flatten("", &file.parse::<toml::Table>().unwrap(), &mut values);
+        let env = env.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+        merge(values, env)
+    }
+
+    const FILE: &str = r#"
+port = 8001
+
+[database]
+url = "postgres://file"
+
+[mail]
+host = "imap.example.ru"
+user = "stock@example.ru"
+pass = "from-file"
+split = ["fenix", "vvk"]
+
+[vvk]
+interval_hours = 12
+"#;
+
+    #[test]
+    fn file_values() {
+        let config = Config::from_values(values(FILE, &[])).unwrap();
+        assert_eq!(config.database_url, "postgres://file");
+        assert_eq!(config.port, 8001);
+        let mail = config.mail.as_ref().unwrap();
+        assert_eq!(mail.pass, "from-file");
+        assert_eq!(mail.split_suppliers, ["fenix", "vvk"]);
+        assert_eq!(config.web_interval("vvk"), 12);
+        assert_eq!(config.web_interval("ortgraph"), DEFAULT_WEB_INTERVAL_HOURS);
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let env = [("PORT", "9000"), ("MAIL_PASS", "from-env")];
+        let config = Config::from_values(values(FILE, &env)).unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.mail.unwrap().pass, "from-env");
+    }
+
+    #[test]
+    fn empty_env_keeps_file_value() {
+        let env = [("MAIL_PASS", ""), ("DATABASE_URL", " ")];
+        let config = Config::from_values(values(FILE, &env)).unwrap();
+        assert_eq!(config.database_url, "postgres://file");
+        assert_eq!(config.mail.unwrap().pass, "from-file");
+    }
+
+    #[test]
+    fn env_disables_file_section() {
+        let config = Config::from_values(values(FILE, &[("MAIL_ENABLED", "false")])).unwrap();
+        assert!(config.mail.is_none());
+    }
+
+    #[test]
+    fn problems_listed_together() {
+        let env = [
+            ("MAIL_HOST", "imap.example.ru"),
+            ("VVK_INTERVAL_HOURS", "0"),
+        ];
+        let error = Config::from_values(values("", &env))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("DATABASE_URL"), "{error}");
+        assert!(error.contains("MAIL_USER, MAIL_PASS"), "{error}");
+        assert!(error.contains("VVK_INTERVAL_HOURS=0"), "{error}");
+    }
+}
@@ -1,5 +1,7 @@
 use std::{error::Error, fmt::Display};
 
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
@@ -7,6 +9,14 @@ pub enum AppError {
     DbError(String),
     ReqwestError(String),
     MailError(String),
+    ConfigError(String),
+    NotFound(String),
+    /// Неверные данные в запросе
+    BadRequest(String),
+    /// Запрос, которому нужен токен API, без действующего токена
+    Unauthorized(String),
+    /// Запрос противоречит данным: поставщик с таким кодом уже есть
+    Conflict(String),
     Custom(String),
 }
 
@@ -17,6 +27,22 @@ impl Display for AppError {
     }
 }
 impl Error for AppError {}
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        if status != StatusCode::INTERNAL_SERVER_ERROR {
+            return (status, axum::Json(self)).into_response();
+        }
+        tracing::error!("{self:?}");
+        (StatusCode::INTERNAL_SERVER_ERROR, axum::Json(self)).into_response()
+    }
+}
 impl From<reqwest::Error> for AppError {
     fn from(value: reqwest::Error) -> Self {
         Self::ReqwestError(value.to_string())
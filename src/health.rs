@@ -0,0 +1,134 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::{config::MailConfig, stock_service::MailClient, utils::sleep_or_cancel};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Результат последних запусков задачи или проверки внешнего сервиса
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RunStatus {
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl RunStatus {
+    /// Последний запуск завершился ошибкой
+    pub fn is_failing(&self) -> bool {
+        match (self.last_success, self.last_failure) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(success), Some(failure)) => failure > success,
+        }
+    }
+}
+
+/// Общее состояние фоновых задач и внешних сервисов для `/health`
+#[derive(Default)]
+pub struct Status {
+    jobs: RwLock<BTreeMap<&'static str, RunStatus>>,
+    checks: RwLock<BTreeMap<&'static str, RunStatus>>,
+}
+
+impl Status {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+    pub async fn job_success(&self, job: &'static str) {
+        record(&self.jobs, job, Ok(())).await
+    }
+    pub async fn job_failure(&self, job: &'static str, e: impl std::fmt::Debug) {
+        record(&self.jobs, job, Err(format!("{e:?}"))).await
+    }
+    pub async fn check_result(&self, component: &'static str, result: Result<(), String>) {
+        record(&self.checks, component, result).await
+    }
+    pub async fn jobs(&self) -> BTreeMap<&'static str, RunStatus> {
+        self.jobs.read().await.clone()
+    }
+    pub async fn checks(&self) -> BTreeMap<&'static str, RunStatus> {
+        self.checks.read().await.clone()
+    }
+}
+
+async fn record(
+    map: &RwLock<BTreeMap<&'static str, RunStatus>>,
+    name: &'static str,
+    result: Result<(), String>,
+) {
+    let mut map = map.write().await;
+    let entry = map.entry(name).or_default();
+    match result {
+        Ok(()) => entry.last_success = Some(Utc::now()),
+        Err(e) => {
+            entry.last_failure = Some(Utc::now());
+            entry.last_error = Some(e);
+        }
+    }
+}
+
+/// Периодически проверяет доступность внешних сервисов
+pub struct Checker {
+    pub mail: Option<MailConfig>,
+    pub moysklad: Option<Arc<rust_moysklad::MoySkladApiClient>>,
+    pub woocommerce: Option<Arc<rust_woocommerce::ApiClient>>,
+}
+
+impl Checker {
+    pub async fn run(self, status: Arc<Status>, shutdown: CancellationToken) {
+        loop {
+            if let Some(mail) = &self.mail {
+                let client = MailClient::new(mail);
+                let result = tokio::time::timeout(
+                    CHECK_TIMEOUT,
+                    tokio::task::spawn_blocking(move || client.check()),
+                )
+                .await;
+                let result = match result {
+                    Ok(Ok(Ok(()))) => Ok(()),
+                    Ok(Ok(Err(e))) => Err(format!("{e:?}")),
+                    Ok(Err(e)) => Err(format!("{e:?}")),
+                    Err(_) => Err("Превышено время ожидания".to_string()),
+                };
+                report("imap", &result);
+                status.check_result("imap", result).await;
+            }
+            if let Some(client) = &self.moysklad {
+                let result = timeout(client.get_all::<rust_moysklad::Currency>()).await;
+                report("moysklad", &result);
+                status.check_result("moysklad", result).await;
+            }
+            if let Some(client) = &self.woocommerce {
+                let result = timeout(client.list_all::<rust_woocommerce::Attribute>()).await;
+                report("woocommerce", &result);
+                status.check_result("woocommerce", result).await;
+            }
+            if sleep_or_cancel(CHECK_INTERVAL.as_secs(), &shutdown).await {
+                break;
+            }
+        }
+    }
+}
+
+async fn timeout<T, E: std::fmt::Debug>(
+    fut: impl std::future::Future<Output = Result<T, E>>,
+) -> Result<(), String> {
+    match tokio::time::timeout(CHECK_TIMEOUT, fut).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("{e:?}")),
+        Err(_) => Err("Превышено время ожидания".to_string()),
+    }
+}
+
+fn report(component: &str, result: &Result<(), String>) {
+    if let Err(e) = result {
+        warn!("Проверка {component} не прошла: {e}");
+    }
+}
@@ -1,22 +1,40 @@
+mod api;
+mod availability;
+mod bot;
+pub mod cli;
+pub mod config;
 mod error;
+mod health;
+mod metrics;
 use std::sync::Arc;
 
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+pub use config::Config;
 pub use error::{AppError, Result};
-use storage::StockStorage;
+use storage::{ImportStorage, StockStorage, SupplierStorage, SystemStorage};
 mod models;
+mod search;
 mod stock_service;
 mod storage;
 mod synchronizer;
 pub mod utils;
 
+/// Сколько ждать завершения фоновых задач после сигнала остановки
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
 pub struct LocalService {
     pool: sqlx::PgPool,
+    config: Config,
 }
 impl LocalService {
-    pub fn new(pool: sqlx::PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: sqlx::PgPool, config: Config) -> Self {
+        Self { pool, config }
     }
-    pub async fn run(&self) {
+    /// Применяет миграции. Таблица `_sqlx_migrations` пересоздается, поэтому
+    /// миграции выполняются заново при каждом запуске и должны быть повторяемыми.
+    pub async fn migrate(&self) -> Result<()> {
         if let Err(e) = sqlx::query("DROP TABLE IF EXISTS _sqlx_migrations")
             .execute(&self.pool)
             .await
@@ -26,29 +44,123 @@ impl LocalService {
         sqlx::migrate!()
             .run(&self.pool)
             .await
-            .expect("Failed to migrate");
-        let ms_token = std::env::var("MS_TOKEN").expect("MS_TOKEN not set");
-        let ms_client = Arc::new(
-            rust_moysklad::MoySkladApiClient::new(ms_token)
-                .expect("Не получилось создать клиент Мой Склад"),
-        );
-        let safira_ck = std::env::var("SAFIRA_CK").expect("SAFIRA_CK not set");
-        let safira_cs = std::env::var("SAFIRA_CS").expect("SAFIRA_CS not set");
-        let safira_host = std::env::var("SAFIRA_HOST").expect("SAFIRA_HOST not set");
-        let safira_client = Arc::new(
-            rust_woocommerce::ApiClient::init(safira_host, safira_ck, safira_cs)
-                .expect("safira_woo_client init error"),
-        );
+            .map_err(|e| AppError::DbError(e.to_string()))?;
+        let stock_storage = StockStorage::new(self.pool.clone());
+        let linked = stock_storage.link_items().await?;
+        if linked > 0 {
+            tracing::info!("Остатки привязаны к каталогу товаров: {linked}");
+        }
+        let filled = stock_storage.fill_search_names().await?;
+        if filled > 0 {
+            tracing::info!("Заполнена строка поиска остатков и товаров каталога: {filled}");
+        }
+        Ok(())
+    }
+    /// Работает до отмены `shutdown`, затем дожидается фоновых задач: импорт сохраняет
+    /// полученные остатки, синхронизация завершает текущий пакет, сервер отвечает на
+    /// начатые запросы
+    pub async fn run(&self, shutdown: CancellationToken) {
+        self.migrate().await.expect("Failed to migrate");
+        let config = &self.config;
+        for (component, enabled) in config.components() {
+            if enabled {
+                tracing::info!("{component}: включен");
+            } else {
+                tracing::warn!("{component}: отключен");
+            }
+        }
+        let mut tasks = JoinSet::new();
         let stock_storage = Arc::new(StockStorage::new(self.pool.clone()));
-        let syncer = synchronizer::Synchronizer::new(
-            ms_client.clone(),
-            safira_client.clone(),
+        let import_storage = Arc::new(ImportStorage::new(self.pool.clone()));
+        let supplier_storage = Arc::new(SupplierStorage::new(self.pool.clone()));
+        let status = health::Status::new();
+        let sync_trigger = Arc::new(tokio::sync::Notify::new());
+        let ms_client = config.moysklad.as_ref().map(|ms_config| {
+            Arc::new(
+                rust_moysklad::MoySkladApiClient::new(ms_config.token.clone())
+                    .expect("Не получилось создать клиент Мой Склад"),
+            )
+        });
+        let safira_client = config.safira.as_ref().map(|safira| {
+            Arc::new(
+                rust_woocommerce::ApiClient::init(
+                    safira.host.clone(),
+                    safira.ck.clone(),
+                    safira.cs.clone(),
+                )
+                .expect("safira_woo_client init error"),
+            )
+        });
+        let checker = health::Checker {
+            mail: config.mail.clone(),
+            moysklad: ms_client.clone(),
+            woocommerce: safira_client.clone(),
+        };
+        tasks.spawn(checker.run(status.clone(), shutdown.clone()));
+        if let Some(ms_client) = ms_client {
+            let syncer = synchronizer::Synchronizer::new(
+                ms_client,
+                safira_client,
+                stock_storage.clone(),
+                supplier_storage.clone(),
+                sync_trigger.clone(),
+                &config.sync,
+                status.clone(),
+                shutdown.clone(),
+            );
+            tasks.spawn(syncer.run());
+        } else {
+            tracing::warn!("Мой Склад не настроен, синхронизация отключена");
+        }
+        if let Some(bot_config) = &config.bot {
+            let bot = bot::Bot::new(bot_config, stock_storage.clone(), sync_trigger.clone())
+                .expect("Не получилось создать бота");
+            tasks.spawn(bot.run(shutdown.clone()));
+        }
+        let addr = format!("0.0.0.0:{port}", port = config.port);
+        let state = api::AppState {
+            stock_storage: stock_storage.clone(),
+            import_storage: import_storage.clone(),
+            system_storage: Arc::new(SystemStorage::new(self.pool.clone())),
+            supplier_storage: supplier_storage.clone(),
+            status: status.clone(),
+            components: config.components(),
+            api_token: config.api_token.clone(),
+        };
+        let server_shutdown = shutdown.clone();
+        tasks.spawn(async move {
+            if let Err(e) = api::serve(addr, state, server_shutdown).await {
+                tracing::error!("{e:?}");
+            }
+        });
+        let stocker = stock_service::Stocker::new(
+            config,
             stock_storage.clone(),
+            import_storage.clone(),
+            supplier_storage.clone(),
+            status.clone(),
+            shutdown.clone(),
         );
-        tokio::spawn(syncer.run());
-        let stocker = stock_service::Stocker::new(stock_storage.clone());
-        if let Err(e) = stocker.run().await {
-            tracing::error!("{e:?}")
+        tasks.spawn(async move {
+            if let Err(e) = stocker.run().await {
+                tracing::error!("{e:?}")
+            }
+        });
+        shutdown.cancelled().await;
+        tracing::info!("Получен сигнал остановки, жду завершения задач");
+        let drain = async {
+            while let Some(result) = tasks.join_next().await {
+                if let Err(e) = result {
+                    tracing::error!("{e:?}");
+                }
+            }
+        };
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, drain).await.is_err() {
+            tracing::warn!(
+                "Задачи не завершились за {SHUTDOWN_TIMEOUT:?}, останавливаю принудительно"
+            );
         }
+        self.pool.close().await;
+        tracing::info!("Сервис остановлен");
     }
 }
@@ -12,14 +12,21 @@
 //
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt().init();
-    let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL not set!");
-    tracing::debug!("DATABASE_URL={db_url}");
-    let pool = sqlx::postgres::PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
-        .await?;
-    let service = friday_api::LocalService::new(pool);
-    service.run().await;
-    Ok(())
+    use friday_api::cli::{Command, USAGE};
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if command == Command::Serve {
+        tracing_subscriber::fmt().init();
+    } else {
+        // Вывод команд идет в stdout, логи не должны в него попадать
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .init();
+    }
+    friday_api::cli::run(command).await
 }
@@ -0,0 +1,175 @@
+use std::{future::Future, sync::LazyLock, time::Instant};
+
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+/// Метрики сервиса в формате Prometheus, отдаются на `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    pub rows_imported: IntCounterVec,
+    pub rows_deleted: IntCounterVec,
+    pub parse_failures: IntCounterVec,
+    pub last_import: GaugeVec,
+    pub api_calls: IntCounterVec,
+    pub api_errors: IntCounterVec,
+    pub sync_duration: HistogramVec,
+    pub sync_products: IntCounterVec,
+    pub sync_products_last_run: IntGaugeVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub fn get() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("friday".to_string()), None)
+            .expect("Ошибка создания реестра метрик");
+        let rows_imported = IntCounterVec::new(
+            Opts::new("stock_rows_imported_total", "Строк остатков сохранено"),
+            &["supplier"],
+        )
+        .expect("metric");
+        let rows_deleted = IntCounterVec::new(
+            Opts::new(
+                "stock_rows_deleted_total",
+                "Строк остатков удалено при замене",
+            ),
+            &["supplier"],
+        )
+        .expect("metric");
+        let parse_failures = IntCounterVec::new(
+            Opts::new(
+                "stock_parse_failures_total",
+                "Файлов остатков, которые не получилось прочитать",
+            ),
+            &["supplier"],
+        )
+        .expect("metric");
+        let last_import = GaugeVec::new(
+            Opts::new(
+                "stock_last_import_timestamp_seconds",
+                "Время последнего сохранения остатков поставщика",
+            ),
+            &["supplier"],
+        )
+        .expect("metric");
+        let api_calls = IntCounterVec::new(
+            Opts::new("external_api_calls_total", "Запросов к внешним API"),
+            &["service", "operation"],
+        )
+        .expect("metric");
+        let api_errors = IntCounterVec::new(
+            Opts::new("external_api_errors_total", "Ошибок запросов к внешним API"),
+            &["service", "operation"],
+        )
+        .expect("metric");
+        let sync_duration = HistogramVec::new(
+            HistogramOpts::new("sync_duration_seconds", "Длительность синхронизации")
+                .buckets(vec![10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 3600.0]),
+            &["result"],
+        )
+        .expect("metric");
+        let sync_products = IntCounterVec::new(
+            Opts::new("sync_products_total", "Товаров изменено синхронизацией"),
+            &["target", "action"],
+        )
+        .expect("metric");
+        let sync_products_last_run = IntGaugeVec::new(
+            Opts::new(
+                "sync_products_last_run",
+                "Товаров изменено последней синхронизацией",
+            ),
+            &["target", "action"],
+        )
+        .expect("metric");
+        for collector in [
+            Box::new(rows_imported.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(rows_deleted.clone()),
+            Box::new(parse_failures.clone()),
+            Box::new(last_import.clone()),
+            Box::new(api_calls.clone()),
+            Box::new(api_errors.clone()),
+            Box::new(sync_duration.clone()),
+            Box::new(sync_products.clone()),
+            Box::new(sync_products_last_run.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("Ошибка регистрации метрики");
+        }
+        Metrics {
+            registry,
+            rows_imported,
+            rows_deleted,
+            parse_failures,
+            last_import,
+            api_calls,
+            api_errors,
+            sync_duration,
+            sync_products,
+            sync_products_last_run,
+        }
+    }
+    /// Текстовый формат для Prometheus
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("Ошибка формирования метрик: {e:?}");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+    pub fn parse_failure(&self, supplier: &str) {
+        self.parse_failures.with_label_values(&[supplier]).inc();
+    }
+    pub fn sync_products(&self, target: &str, action: &str, count: usize) {
+        self.sync_products
+            .with_label_values(&[target, action])
+            .inc_by(count as u64);
+        self.sync_products_last_run
+            .with_label_values(&[target, action])
+            .set(count as i64);
+    }
+    /// Считает вызов внешнего API и его ошибку
+    pub async fn track<T, E>(
+        &self,
+        service: &str,
+        operation: &str,
+        fut: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        self.api_calls
+            .with_label_values(&[service, operation])
+            .inc();
+        let result = fut.await;
+        if result.is_err() {
+            self.api_errors
+                .with_label_values(&[service, operation])
+                .inc();
+        }
+        result
+    }
+    /// Засекает длительность синхронизации
+    pub fn sync_timer(&self) -> SyncTimer {
+        SyncTimer {
+            started: Instant::now(),
+        }
+    }
+}
+
+pub struct SyncTimer {
+    started: Instant,
+}
+
+impl SyncTimer {
+    pub fn finish(self, success: bool) {
+        let result = if success { "success" } else { "failure" };
+        get()
+            .sync_duration
+            .with_label_values(&[result])
+            .observe(self.started.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::ParseReport;
+
+/// Загрузка остатков одного поставщика: сколько строк сохранено или почему не
+/// получилось, и что сделано с письмами, из которых взяты файлы
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct Import {
+    pub id: uuid::Uuid,
+    pub supplier: String,
+    /// Откуда получены файлы: `mail` или `web`
+    pub source: String,
+    pub items: i64,
+    pub error: Option<String>,
+    pub mail_uids: Vec<i64>,
+    pub mail_action: Option<String>,
+    /// Загрузка из более нового письма, вместо которого это письмо не применялось
+    pub superseded_by: Option<uuid::Uuid>,
+    /// Отчет разбора файлов, если до разбора дошло
+    pub report: Option<sqlx::types::Json<ParseReport>>,
+    pub created: DateTime<Utc>,
+}
@@ -1,3 +1,13 @@
+mod import;
+mod parse_report;
+mod raw_file;
 mod stock;
+mod supplier;
+mod supplier_item;
 
+pub use import::*;
+pub use parse_report::*;
+pub use raw_file::*;
 pub use stock::*;
+pub use supplier::*;
+pub use supplier_item::*;
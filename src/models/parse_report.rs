@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fmt::Display;
+
+/// Сколько пропущенных строк и заголовков перечисляется в отчете, остальные
+/// только считаются
+pub const MAX_REPORTED_ROWS: usize = 200;
+
+/// Как разобраны файлы поставщика: сколько строк прочитано, сколько стало
+/// остатками и почему остальные пропущены
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParseReport {
+    pub rows_seen: usize,
+    pub rows_accepted: usize,
+    pub rows_skipped: usize,
+    /// Строки шапки таблицы и заголовков групп
+    pub rows_headers: usize,
+    /// Первые `MAX_REPORTED_ROWS` строк заголовков
+    pub headers: Vec<SheetRow>,
+    /// Первые `MAX_REPORTED_ROWS` пропущенных строк
+    pub skipped: Vec<SkippedRow>,
+    /// Листы без обязательных колонок в файлах, где таблица остатков нашлась
+    /// на другом листе
+    #[serde(default)]
+    pub skipped_sheets: Vec<String>,
+    /// Файлы, которые не получилось прочитать, и файлы без таблицы остатков
+    pub errors: Vec<String>,
+}
+
+/// Строка листа, номер как в Excel, с единицы
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SheetRow {
+    pub sheet: String,
+    pub row: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedRow {
+    pub sheet: String,
+    pub row: usize,
+    pub reason: String,
+}
+
+impl ParseReport {
+    pub fn header(&mut self, row: SheetRow) {
+        self.rows_headers += 1;
+        if self.headers.len() < MAX_REPORTED_ROWS {
+            self.headers.push(row);
+        }
+    }
+    pub fn skip(&mut self, row: SkippedRow) {
+        self.rows_skipped += 1;
+        if self.skipped.len() < MAX_REPORTED_ROWS {
+            self.skipped.push(row);
+        }
+    }
+}
+
+impl Display for ParseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "📊 Строк: прочитано {}, принято {}, пропущено {}, заголовков {}",
+            self.rows_seen, self.rows_accepted, self.rows_skipped, self.rows_headers
+        )?;
+        for error in &self.errors {
+            write!(f, "\n❌ {error}")?;
+        }
+        for sheet in &self.skipped_sheets {
+            write!(f, "\n⏭ {sheet}, лист пропущен")?;
+        }
+        for skipped in &self.skipped {
+            write!(
+                f,
+                "\n⏭ {}:{} {}",
+                skipped.sheet, skipped.row, skipped.reason
+            )?;
+        }
+        if self.rows_skipped > self.skipped.len() {
+            write!(
+                f,
+                "\n… и еще {} пропущенных строк",
+                self.rows_skipped - self.skipped.len()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Сохраненный файл остатков. Файл хранится один раз на поставщика по SHA-256
+/// содержимого, `received` это дата самого нового получения.
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct RawFile {
+    pub sha256: String,
+    pub supplier: String,
+    /// Откуда получен файл: `mail` или `web`
+    pub source: String,
+    pub filename: String,
+    pub size: i64,
+    pub received: DateTime<Utc>,
+    pub created: DateTime<Utc>,
+}
@@ -1,20 +1,81 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::fmt;
 use std::fmt::Display;
 
-#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+use crate::search;
+
+/// Пространство имен UUIDv5 для идентификаторов товаров поставщиков
+const ITEM_NAMESPACE: uuid::Uuid = uuid::uuid!("5b0d7c2e-9f4a-4c61-8e3b-2a7f1d6c9e05");
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, FromRow)]
 pub struct Stock {
     pub id: uuid::Uuid,
+    /// Товар поставщика из каталога, одинаковый во всех загрузках, см. [`Stock::item_key`]
+    pub item_id: uuid::Uuid,
     pub supplier: String,
     pub name: String,
+    /// Свободный остаток, который можно продать
     pub stock: f64,
+    /// Весь остаток на складе вместе с резервом
+    pub on_hand: Option<f64>,
+    pub reserved: Option<f64>,
+    /// Ожидаемый приход и его дата
+    pub incoming: Option<f64>,
+    pub arrival: Option<NaiveDate>,
     pub updated: DateTime<Utc>,
+    /// Свойства товара, если поставщик дает их отдельными колонками
+    pub brand: Option<String>,
+    pub collection: Option<String>,
+    pub color: Option<String>,
+    /// Ширина рулона в метрах
+    pub width: Option<f64>,
+    pub article: Option<String>,
 }
 impl Stock {
+    /// Постоянный идентификатор товара: UUIDv5 от поставщика, нормализованного
+    /// наименования и свойств. Пока поставщик пишет товар так же, идентификатор
+    /// от загрузки к загрузке не меняется.
+    pub fn item_key(&self) -> uuid::Uuid {
+        let attributes = [&self.brand, &self.collection, &self.color, &self.article]
+            .map(|a| a.as_deref().map(search::normalize).unwrap_or_default());
+        let width = self.width.map(|w| w.to_string()).unwrap_or_default();
+        let key = format!(
+            "{}\n{}\n{}\n{width}",
+            self.supplier,
+            search::normalize(&self.name),
+            attributes.join("\n")
+        );
+        uuid::Uuid::new_v5(&ITEM_NAMESPACE, key.as_bytes())
+    }
+    /// Добавляет остатки другой строки того же товара: поставщики пишут товар
+    /// несколькими строками по рулонам или складам. Из двух дат прихода берется
+    /// поздняя, к ней придет весь приход.
+    pub fn merge(&mut self, other: &Stock) {
+        let sum = |a: Option<f64>, b: Option<f64>| a.into_iter().chain(b).reduce(|a, b| a + b);
+        self.stock += other.stock;
+        self.on_hand = sum(self.on_hand, other.on_hand);
+        self.reserved = sum(self.reserved, other.reserved);
+        self.incoming = sum(self.incoming, other.incoming);
+        self.arrival = self.arrival.max(other.arrival);
+    }
+    /// Сколько можно продать: свободный остаток и приход, который ожидается
+    /// не позже `until`. Приход без даты не учитывается.
+    pub fn available(&self, until: Option<NaiveDate>) -> f64 {
+        let incoming = match (self.incoming, self.arrival, until) {
+            (Some(incoming), Some(arrival), Some(until)) if arrival <= until => incoming,
+            _ => 0.0,
+        };
+        self.stock + incoming
+    }
     pub fn safe_print(&self) -> String {
-        format!("📛 Наименование: {}\n📦 Остаток: {:.2}\n🕒 Обновлено: {}\n", self.name, self.stock, self.updated.format("%d.%m.%Y %H:%M"))
+        format!(
+            "📛 Наименование: {}\n📦 Остаток: {:.2}\n🕒 Обновлено: {}\n",
+            self.name,
+            self.stock,
+            self.updated.format("%d.%m.%Y %H:%M")
+        )
     }
 }
 
@@ -22,7 +83,143 @@ impl Display for Stock {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "🏭 Поставщик: {}", self.supplier)?;
         writeln!(f, "📛 Наименование: {}", self.name)?;
+        if let Some(article) = &self.article {
+            writeln!(f, "🔖 Артикул: {article}")?;
+        }
+        if let Some(brand) = &self.brand {
+            writeln!(f, "🏷 Бренд: {brand}")?;
+        }
+        if let Some(collection) = &self.collection {
+            writeln!(f, "📚 Коллекция: {collection}")?;
+        }
+        if let Some(color) = &self.color {
+            writeln!(f, "🎨 Цвет: {color}")?;
+        }
+        if let Some(width) = self.width {
+            writeln!(f, "📏 Ширина: {width} м")?;
+        }
         writeln!(f, "📦 Остаток: {:.2}", self.stock)?;
+        if let Some(reserved) = self.reserved {
+            writeln!(f, "🔒 Резерв: {reserved:.2}")?;
+        }
+        if let Some(incoming) = self.incoming {
+            match self.arrival {
+                Some(arrival) => writeln!(
+                    f,
+                    "🚚 В пути: {incoming:.2}, ожидается {}",
+                    arrival.format("%d.%m.%Y")
+                )?,
+                None => writeln!(f, "🚚 В пути: {incoming:.2}")?,
+            }
+        }
+        write!(f, "🕒 Обновлено: {}", self.updated.format("%d.%m.%Y %H:%M"))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct SupplierSummary {
+    pub supplier: String,
+    pub items: i64,
+    pub updated: DateTime<Utc>,
+}
+
+impl Display for SupplierSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "🏭 Поставщик: {}", self.supplier)?;
+        writeln!(f, "🔢 Позиций: {}", self.items)?;
         write!(f, "🕒 Обновлено: {}", self.updated.format("%d.%m.%Y %H:%M"))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(name: &str) -> Stock {
+        Stock {
+            supplier: "fenix".to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 11, day).unwrap()
+    }
+
+    #[test]
+    fn item_key_ignores_case_spacing_and_homoglyphs() {
+        let key = stock("Ковер ВЕЖ 2,0х3,0").item_key();
+        assert_eq!(stock("ковер  веж 2,0х3,0").item_key(), key);
+        // Латинские буквы, похожие на русские, и лишние нули
+        assert_eq!(stock("KOBEP BEЖ 2x3").item_key(), key);
+        let mut colored = stock("Ковер ВЕЖ 2,0х3,0");
+        colored.color = Some(" Беж ".to_string());
+        let mut latin = stock("KOBEP BEЖ 2x3");
+        latin.color = Some("БEЖ".to_string());
+        assert_eq!(colored.item_key(), latin.item_key());
+    }
+
+    #[test]
+    fn item_key_differs_by_supplier_and_attributes() {
+        let key = stock("Ковер ВЕЖ").item_key();
+        let mut other = stock("Ковер ВЕЖ");
+        other.supplier = "fox".to_string();
+        assert_ne!(other.item_key(), key);
+        let mut wide = stock("Ковер ВЕЖ");
+        wide.width = Some(4.0);
+        assert_ne!(wide.item_key(), key);
+        let mut article = stock("Ковер ВЕЖ");
+        article.article = Some("1001".to_string());
+        assert_ne!(article.item_key(), key);
+        // Одинаковые значения в разных свойствах дают разные товары
+        let mut brand = stock("Ковер ВЕЖ");
+        brand.brand = Some("Fenix".to_string());
+        let mut collection = stock("Ковер ВЕЖ");
+        collection.collection = Some("Fenix".to_string());
+        assert_ne!(brand.item_key(), collection.item_key());
+    }
+
+    #[test]
+    fn merge_sums_values() {
+        let mut row = stock("Ковер");
+        row.stock = 10.0;
+        row.on_hand = Some(12.0);
+        row.incoming = Some(5.0);
+        let mut other = stock("Ковер");
+        other.stock = 2.5;
+        other.on_hand = Some(3.0);
+        other.reserved = Some(1.0);
+        row.merge(&other);
+        assert_eq!(row.stock, 12.5);
+        assert_eq!(row.on_hand, Some(15.0));
+        // Значение одной из строк остается, пустые в обеих не появляются
+        assert_eq!(row.reserved, Some(1.0));
+        assert_eq!(row.incoming, Some(5.0));
+        row.merge(&stock("Ковер"));
+        assert_eq!(row.reserved, Some(1.0));
+        let mut empty = stock("Ковер");
+        empty.merge(&stock("Ковер"));
+        assert_eq!(empty.on_hand, None);
+        assert_eq!(empty.incoming, None);
+    }
+
+    #[test]
+    fn merge_takes_later_arrival() {
+        let mut row = stock("Ковер");
+        row.arrival = Some(date(20));
+        let mut other = stock("Ковер");
+        other.arrival = Some(date(5));
+        row.merge(&other);
+        assert_eq!(row.arrival, Some(date(20)));
+        other.arrival = Some(date(25));
+        row.merge(&other);
+        assert_eq!(row.arrival, Some(date(25)));
+        // Строка без даты прихода дату не сбрасывает
+        row.merge(&stock("Ковер"));
+        assert_eq!(row.arrival, Some(date(25)));
+        let mut undated = stock("Ковер");
+        undated.merge(&other);
+        assert_eq!(undated.arrival, Some(date(25)));
+    }
+}
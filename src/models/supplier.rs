@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Поставщик из справочника: код, которым помечены остатки, и сведения о нем
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct Supplier {
+    pub code: String,
+    /// Название для людей: «Опус-Контракт», «Братец Лис»
+    pub name: String,
+    /// Адреса, с которых приходят письма с остатками
+    pub emails: Vec<String>,
+    pub contacts: Option<String>,
+    /// Откуда берутся остатки: `mail` или `web`
+    pub source: String,
+    /// Срок поставки со склада поставщика в рабочих днях
+    pub lead_time_days: Option<i32>,
+    /// Вложения без имени тоже считаются остатками, так присылает Опус
+    pub unnamed_attachments: bool,
+    /// Остатки отключенного поставщика не загружаются
+    pub enabled: bool,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+/// Изменяемые поля поставщика для создания и изменения через API
+#[derive(Clone, Debug, Deserialize)]
+pub struct SupplierInput {
+    pub name: String,
+    #[serde(default)]
+    pub emails: Vec<String>,
+    pub contacts: Option<String>,
+    #[serde(default = "default_source")]
+    pub source: String,
+    pub lead_time_days: Option<i32>,
+    #[serde(default)]
+    pub unnamed_attachments: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// Поставщик, от которого приходят письма с остатками
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MailSender {
+    pub supplier: String,
+    pub unnamed_attachments: bool,
+}
+
+fn default_source() -> String {
+    "mail".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::Stock;
+
+/// Товар из каталога поставщика: когда впервые и когда последний раз был в остатках.
+/// Идентификатор совпадает с `item_id` строк остатков.
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct SupplierItem {
+    pub id: uuid::Uuid,
+    pub supplier: String,
+    pub name: String,
+    pub brand: Option<String>,
+    pub collection: Option<String>,
+    pub color: Option<String>,
+    pub width: Option<f64>,
+    pub article: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl From<&Stock> for SupplierItem {
+    fn from(stock: &Stock) -> Self {
+        Self {
+            id: stock.item_id,
+            supplier: stock.supplier.clone(),
+            name: stock.name.clone(),
+            brand: stock.brand.clone(),
+            collection: stock.collection.clone(),
+            color: stock.color.clone(),
+            width: stock.width,
+            article: stock.article.clone(),
+            first_seen: stock.updated,
+            last_seen: stock.updated,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+/// Латинские буквы, которые пишутся так же, как кириллические.
+/// Все приводится к латинскому написанию, чтобы "ВЕЖ" и "BEЖ" совпадали.
+const HOMOGLYPHS: [(char, char); 13] = [
+    ('А', 'A'),
+    ('В', 'B'),
+    ('Е', 'E'),
+    ('Ё', 'E'),
+    ('К', 'K'),
+    ('М', 'M'),
+    ('Н', 'H'),
+    ('О', 'O'),
+    ('Р', 'P'),
+    ('С', 'C'),
+    ('Т', 'T'),
+    ('У', 'Y'),
+    ('Х', 'X'),
+];
+/// Единицы длины (уже в латинском написании), которые отбрасываются после числа: "4м", "4 м", "4m"
+const LENGTH_UNITS: [&str; 4] = ["M", "METP", "METPA", "METPOB"];
+
+/// Приводит строку к виду для поиска: верхний регистр, латиница вместо похожей кириллицы,
+/// точка вместо десятичной запятой, числа без лишних нулей ("4,0" -> "4") и без единиц длины.
+/// Размеры через "х" нормализуются по числам: "1,50х2,0" -> "1.5X2"
+pub fn normalize(input: impl AsRef<str>) -> String {
+    tokens(input).join(" ")
+}
+
+/// Разбивает строку на нормализованные слова
+pub fn tokens(input: impl AsRef<str>) -> Vec<String> {
+    let upper = input
+        .as_ref()
+        .to_uppercase()
+        .chars()
+        .map(|c| {
+            HOMOGLYPHS
+                .iter()
+                .find(|(cyr, _)| *cyr == c)
+                .map(|(_, lat)| *lat)
+                .unwrap_or(c)
+        })
+        .collect::<String>();
+    let mut result: Vec<String> = Vec::new();
+    for word in upper.split(|c: char| !(c.is_alphanumeric() || c == '.' || c == ',')) {
+        let word = word.trim_matches(|c| c == '.' || c == ',');
+        if word.is_empty() {
+            continue;
+        }
+        if LENGTH_UNITS.contains(&word) && result.last().is_some_and(|w| is_number(w)) {
+            continue;
+        }
+        let word = word.replace(',', ".");
+        let word = LENGTH_UNITS
+            .iter()
+            .find_map(|unit| {
+                word.strip_suffix(unit)
+                    .filter(|number| is_number(number) || normalize_size(number).is_some())
+                    .map(String::from)
+            })
+            .unwrap_or(word);
+        if is_number(&word) {
+            result.push(normalize_number(&word));
+        } else if let Some(size) = normalize_size(&word) {
+            result.push(size);
+        } else {
+            result.push(word);
+        }
+    }
+    result
+}
+
+fn is_number(word: &str) -> bool {
+    !word.is_empty()
+        && word.parse::<f64>().is_ok()
+        && word.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Размер из чисел через "X" (кириллическая "Х" к этому времени уже латинская),
+/// каждое число без лишних нулей
+fn normalize_size(word: &str) -> Option<String> {
+    let parts = word.split('X').collect::<Vec<_>>();
+    if parts.len() < 2 || !parts.iter().all(|part| is_number(part)) {
+        return None;
+    }
+    let parts = parts.into_iter().map(normalize_number).collect::<Vec<_>>();
+    Some(parts.join("X"))
+}
+
+fn normalize_number(word: &str) -> String {
+    if word.contains('.') {
+        word.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn homoglyphs_match_latin() {
+        // Кириллица и латиница вперемешку, как в файлах поставщиков
+        assert_eq!(normalize("Ковер ВЕЖ"), normalize("KOBEP BEЖ"));
+        assert_eq!(normalize("ёлка"), "EЛKA");
+        assert_eq!(normalize("Ковер ВЕЖ"), "KOBEP BEЖ");
+    }
+
+    #[test]
+    fn numbers_without_extra_zeros() {
+        assert_eq!(tokens("ширина 4,0"), ["ШИPИHA", "4"]);
+        assert_eq!(tokens("0,50 10 100.0"), ["0.5", "10", "100"]);
+        // Точки и запятые по краям слова это знаки препинания
+        assert_eq!(tokens(".5"), ["5"]);
+        assert_eq!(tokens("Дорожка 0,8."), ["ДOPOЖKA", "0.8"]);
+    }
+
+    #[test]
+    fn sizes_normalized_by_numbers() {
+        assert_eq!(tokens("Ковер 1,50х2,0"), ["KOBEP", "1.5X2"]);
+        assert_eq!(normalize("1,5х2"), normalize("1.50x2.00"));
+        assert_eq!(normalize("1,5х2"), normalize("1.5X2"));
+        assert_eq!(tokens("2,0х3,0х0,5"), ["2X3X0.5"]);
+        assert_eq!(tokens("2х3м"), ["2X3"]);
+        // Не размеры остаются как есть
+        assert_eq!(tokens("LUX 2x"), ["LUX", "2X"]);
+        assert_eq!(tokens("x2"), ["X2"]);
+    }
+
+    #[test]
+    fn length_units_dropped_after_numbers() {
+        assert_eq!(tokens("ширина 4 м"), ["ШИPИHA", "4"]);
+        assert_eq!(tokens("ширина 4м"), ["ШИPИHA", "4"]);
+        assert_eq!(tokens("ширина 4m"), ["ШИPИHA", "4"]);
+        assert_eq!(tokens("рулон 3,5 метра"), ["PYЛOH", "3.5"]);
+        // Без числа перед ней «м» это слово
+        assert_eq!(tokens("м ковер"), ["M", "KOBEP"]);
+        assert_eq!(normalize("4 м"), normalize("4,0м"));
+    }
+
+    #[test]
+    fn separators_split_words() {
+        assert_eq!(
+            tokens("Loop-Classic/100 (беж)"),
+            ["LOOP", "CLASSIC", "100", "БEЖ"]
+        );
+        assert!(tokens("  — ").is_empty());
+    }
+}
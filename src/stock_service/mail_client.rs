@@ -1,85 +1,261 @@
-use chrono::TimeZone;
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, TimeZone, Utc};
 use mail_parser::MimeHeaders;
 use tracing::{error, info};
 
-use crate::Result;
+use crate::{
+    config::{MailActions, MailConfig},
+    models::MailSender,
+    AppError, Result,
+};
 
-use super::FetchMap;
+use super::{FetchMap, SourceFile};
 const QUERY: &str = "RFC822";
-const UID_QUERY: &str = "UID";
 const INBOX: &str = "INBOX";
+/// Имя для вложений без имени у поставщиков с `unnamed_attachments`
+const UNNAMED_ATTACHMENT: &str = "attachment";
+/// Сколько ждать получения почты, прежде чем считать сервер зависшим
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Сколько ждать новых писем в IDLE до переподключения, RFC 2177 советует не больше 29 минут
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Чем закончилось ожидание новых писем
+#[derive(Debug, PartialEq, Eq)]
+pub enum MailWait {
+    /// В ящике что-то изменилось, пора забирать почту
+    Changed,
+    /// Изменений не было, соединение закрыто по таймауту
+    TimedOut,
+    /// Сервер не поддерживает IDLE, остается опрос по расписанию
+    Unsupported,
+}
+
+/// Письмо поставщика с файлами остатков
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub uid: Option<u32>,
+    pub received: DateTime<Utc>,
+    pub files: Vec<SourceFile>,
+}
 
 #[derive(Clone)]
 pub struct MailClient {
-    user: String,
-    pass: String,
-    host: String,
-    last_fetched_uid: u32,
+    config: MailConfig,
+    /// Поставщики по адресам отправителей из справочника поставщиков
+    senders: HashMap<String, MailSender>,
+    /// Самый большой прочитанный UID. UID в ящике только растут, поэтому
+    /// новые письма это письма с большим UID, даже если прочитанные уже перенесены
+    last_fetched_uid: Option<u32>,
+    /// UID примененных писем из последнего получения почты по поставщикам
+    pending: HashMap<String, Vec<u32>>,
+    /// Письма из последнего получения почты, вместо которых применены более новые
+    superseded: HashMap<String, Vec<Mail>>,
 }
 impl MailClient {
-    pub fn new(user: String, pass: String, host: String) -> Result<MailClient> {
-        let mut mail_client = MailClient {
-            user,
-            pass,
-            host,
-            last_fetched_uid: 0,
-        };
-        let mut session = mail_client.session()?;
-        session.select(INBOX)?;
-        let msg_count = session.search("ALL")?.len();
-        let range = format!("{count}", count = msg_count - 200);
-        let first_uid = session
-            .fetch(range, UID_QUERY)?
-            .iter()
-            .next()
-            .ok_or(crate::error::AppError::Custom(
-                "Ошибка при получении стартового UID".into(),
-            ))?
-            .uid
-            .ok_or(crate::error::AppError::Custom(
-                "Ошибка при получении стартового UID".into(),
-            ))?;
-        mail_client.last_fetched_uid = first_uid;
-        session.logout()?;
-        Ok(mail_client)
+    pub fn new(config: &MailConfig) -> MailClient {
+        MailClient {
+            config: config.clone(),
+            senders: HashMap::new(),
+            last_fetched_uid: None,
+            pending: HashMap::new(),
+            superseded: HashMap::new(),
+        }
+    }
+    /// Задает отправителей, письма которых считаются остатками поставщиков
+    pub fn set_senders(&mut self, senders: HashMap<String, MailSender>) {
+        self.senders = senders;
+    }
+    /// Включено ли ожидание писем через IDLE
+    pub fn idle_enabled(&self) -> bool {
+        self.config.idle
+    }
+    /// UID писем в ящике по возрастанию
+    fn uids(session: &mut imap::Session<Box<dyn imap::ImapConnection>>) -> Result<Vec<u32>> {
+        let mut uids = session.uid_search("ALL")?.into_iter().collect::<Vec<_>>();
+        uids.sort_unstable();
+        Ok(uids)
     }
     fn session(&self) -> Result<imap::Session<Box<dyn imap::ImapConnection>>> {
-        let client = imap::ClientBuilder::new(&self.host, 993)
+        let client = imap::ClientBuilder::new(&self.config.host, 993)
             .danger_skip_tls_verify(true)
             .connect()?;
-        let mut session = client.login(&self.user, &self.pass).map_err(|e| e.0)?;
+        let mut session = client
+            .login(&self.config.user, &self.config.pass)
+            .map_err(|e| e.0)?;
         session.select(INBOX)?;
         Ok(session)
     }
-    pub fn fetch(&mut self) -> Result<FetchMap> {
-        info!("Получаю почту");
-        let mut supmap = std::collections::HashMap::new();
-        supmap.insert("vvolodin@opuscontract.ru", "opus");
-        supmap.insert("sales@bratec-lis.com", "fox");
-        supmap.insert("rassilka@fancyfloor.ru", "fancy");
-        supmap.insert("ulyana.boyko@carpetland.ru", "carpetland");
-        supmap.insert("dealer@kover-zefir.ru", "zefir");
-        supmap.insert("almaz2008@yandex.ru", "fenix");
+    /// Проверяет, что сервер доступен и логин с паролем подходят
+    pub fn check(&self) -> Result<()> {
         let mut session = self.session()?;
-        let msg_count = session.search("ALL")?.len();
-        let last_uid_q = format!("{msg_count}");
-        let last_uid = session
-            .fetch(last_uid_q, UID_QUERY)?
+        session.logout()?;
+        Ok(())
+    }
+    /// Получает новые письма в пуле блокирующих задач, чтобы синхронный IMAP не
+    /// занимал потоки асинхронного рантайма. Если сервер завис, через `FETCH_TIMEOUT`
+    /// возвращается ошибка, а следующая попытка читает письма с прежнего UID.
+    pub async fn fetch(&mut self) -> Result<FetchMap> {
+        let mut worker = self.clone();
+        let task = tokio::task::spawn_blocking(move || worker.fetch_sync().map(|m| (worker, m)));
+        match tokio::time::timeout(FETCH_TIMEOUT, task).await {
+            Ok(Ok(Ok((worker, m)))) => {
+                *self = worker;
+                Ok(m)
+            }
+            Ok(Ok(Err(e))) => Err(e),
+            Ok(Err(e)) => Err(AppError::Custom(format!("{e:?}"))),
+            Err(_) => Err(AppError::Custom(
+                "Превышено время ожидания почтового сервера".into(),
+            )),
+        }
+    }
+    /// Ждет новых писем через IDLE в пуле блокирующих задач. Каждое ожидание идет
+    /// в новом соединении, так что оборванное сервером соединение переоткрывается
+    /// при следующем вызове.
+    pub async fn wait(&self) -> Result<MailWait> {
+        let worker = self.clone();
+        let task = tokio::task::spawn_blocking(move || worker.wait_sync());
+        match tokio::time::timeout(IDLE_TIMEOUT + FETCH_TIMEOUT, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(AppError::Custom(format!("{e:?}"))),
+            Err(_) => Err(AppError::Custom(
+                "Превышено время ожидания почтового сервера".into(),
+            )),
+        }
+    }
+    /// UID примененных писем поставщика из последнего получения почты
+    pub fn uids_of(&self, supplier: &str) -> Vec<u32> {
+        self.pending.get(supplier).cloned().unwrap_or_default()
+    }
+    /// Устаревшие письма поставщиков из последнего получения почты, старые первыми
+    pub fn superseded(&self) -> &HashMap<String, Vec<Mail>> {
+        &self.superseded
+    }
+    /// Письмо вместо неразобравшегося: самое новое из оставшихся устаревших
+    pub fn fallback(superseded: &mut Vec<Mail>) -> Option<Mail> {
+        superseded.pop()
+    }
+    /// Отмечает письма поставщика после загрузки: ставит флаг и переносит в папку
+    /// для успешных или неудачных загрузок. Возвращает описание сделанного или
+    /// `None`, если для поставщика действий нет.
+    pub async fn mark(
+        &self,
+        supplier: &str,
+        uids: Vec<u32>,
+        success: bool,
+    ) -> Result<Option<String>> {
+        let actions = self.config.actions(supplier);
+        if actions.is_empty() || uids.is_empty() {
+            return Ok(None);
+        }
+        let worker = self.clone();
+        let task = tokio::task::spawn_blocking(move || worker.mark_sync(&uids, &actions, success));
+        match tokio::time::timeout(FETCH_TIMEOUT, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(AppError::Custom(format!("{e:?}"))),
+            Err(_) => Err(AppError::Custom(
+                "Превышено время ожидания почтового сервера".into(),
+            )),
+        }
+    }
+    fn mark_sync(
+        &self,
+        uids: &[u32],
+        actions: &MailActions,
+        success: bool,
+    ) -> Result<Option<String>> {
+        let uid_set = uids
             .iter()
-            .next()
-            .ok_or(crate::error::AppError::Custom("NO LAST FETCH".into()))?
-            .uid
-            .ok_or(crate::error::AppError::Custom(
-                "Ошибка при получении последнего UID".into(),
-            ))?;
-        if self.last_fetched_uid == last_uid {
-            return Ok(std::collections::HashMap::new());
+            .map(|uid| uid.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let folder = if success {
+            actions.processed_folder.as_ref()
+        } else {
+            actions.errors_folder.as_ref()
+        };
+        let mut done = Vec::new();
+        let mut session = self.session()?;
+        if let Some(flag) = &actions.flag {
+            session.uid_store(&uid_set, format!("+FLAGS ({flag})"))?;
+            done.push(format!("флаг {flag}"));
+        }
+        if let Some(folder) = folder {
+            if session.list(None, Some(folder))?.is_empty() {
+                session.create(folder)?;
+            }
+            let capabilities = session.capabilities()?;
+            if capabilities.has_str("MOVE") {
+                session.uid_mv(&uid_set, folder)?;
+                done.push(format!("перенесено в {folder}"));
+            } else {
+                session.uid_copy(&uid_set, folder)?;
+                session.uid_store(&uid_set, "+FLAGS (\\Deleted)")?;
+                // Обычный EXPUNGE удалил бы и письма, отмеченные другими клиентами,
+                // поэтому без UIDPLUS наши письма остаются отмеченными к удалению
+                if capabilities.has_str("UIDPLUS") {
+                    session.uid_expunge(&uid_set)?;
+                    done.push(format!("перенесено в {folder}"));
+                } else {
+                    done.push(format!("скопировано в {folder}, отмечено к удалению"));
+                }
+            }
         }
-        let q = format!("{first_uid}:{last_uid}", first_uid = self.last_fetched_uid);
+        session.logout()?;
+        Ok((!done.is_empty()).then(|| done.join(", ")))
+    }
+    fn wait_sync(&self) -> Result<MailWait> {
+        let mut session = self.session()?;
+        if !session.capabilities()?.has_str("IDLE") {
+            session.logout()?;
+            return Ok(MailWait::Unsupported);
+        }
+        // Письма, пришедшие между получением почты и началом ожидания: UID больше
+        // прочитанного, даже если прочитанные письма уже перенесены из ящика
+        let last_uid = Self::uids(&mut session)?.last().copied();
+        if last_uid.is_some_and(|last| self.last_fetched_uid.is_some_and(|uid| last > uid)) {
+            session.logout()?;
+            return Ok(MailWait::Changed);
+        }
+        let outcome = session
+            .idle()
+            .timeout(IDLE_TIMEOUT)
+            .keepalive(false)
+            .wait_while(imap::extensions::idle::stop_on_any)?;
+        session.logout()?;
+        match outcome {
+            imap::extensions::idle::WaitOutcome::MailboxChanged => Ok(MailWait::Changed),
+            imap::extensions::idle::WaitOutcome::TimedOut => Ok(MailWait::TimedOut),
+        }
+    }
+    fn fetch_sync(&mut self) -> Result<FetchMap> {
+        info!("Получаю почту");
+        self.pending.clear();
+        self.superseded.clear();
+        let mut session = self.session()?;
+        let uids = Self::uids(&mut session)?;
+        let Some(&last_uid) = uids.last() else {
+            session.logout()?;
+            return Ok(std::collections::HashMap::new());
+        };
+        // При первом запуске читаются последние 200 писем, потом только письма
+        // после последнего прочитанного: оно уже применено. Самые новые письма
+        // могли быть перенесены из ящика, тогда последний UID в ящике меньше
+        // прочитанного, и новых писем нет
+        let first_uid = match self.last_fetched_uid {
+            Some(uid) if last_uid <= uid => {
+                session.logout()?;
+                return Ok(std::collections::HashMap::new());
+            }
+            Some(uid) => uid + 1,
+            None => uids[uids.len().saturating_sub(200)],
+        };
+        let q = format!("{first_uid}:{last_uid}");
         let fetches = session.uid_fetch(q, QUERY)?;
         info!("Получено {len} писем", len = fetches.len());
-        self.last_fetched_uid = last_uid;
-        let mut m = std::collections::HashMap::new();
+        self.last_fetched_uid = self.last_fetched_uid.max(Some(last_uid));
+        let mut candidates = HashMap::<String, Vec<Mail>>::new();
         for fetch in fetches.iter() {
             let fetch_date = fetch.internal_date().map(|d| d.to_utc());
             if let Some(body) = fetch.body() {
@@ -89,17 +265,26 @@ impl MailClient {
                         .and_then(|a| a.first().and_then(|s| s.address()))
                         .map(|s| s.to_lowercase())
                         .unwrap_or_default();
-                    if let Some(supplier) = supmap.get(sender.as_str()) {
+                    if let Some(MailSender {
+                        supplier,
+                        unnamed_attachments,
+                    }) = self.senders.get(&sender)
+                    {
                         let attachments = parsed
                             .attachments()
                             .flat_map(|a| {
                                 if a.attachment_name().is_some_and(|n| {
                                     n.to_lowercase().contains("склад")
                                         || n.to_lowercase().contains("остат")
-                                }) || (sender == "vvolodin@opuscontract.ru"
-                                    && a.attachment_name().is_none())
+                                }) || (*unnamed_attachments && a.attachment_name().is_none())
                                 {
-                                    Some(a.contents().to_vec())
+                                    Some(SourceFile {
+                                        name: a
+                                            .attachment_name()
+                                            .unwrap_or(UNNAMED_ATTACHMENT)
+                                            .to_string(),
+                                        content: a.contents().to_vec(),
+                                    })
                                 } else {
                                     None
                                 }
@@ -141,13 +326,185 @@ impl MailClient {
                                 error!("Не получилось прочитать дату письма {supplier}");
                                 chrono::Utc::now()
                             };
-                            m.insert(supplier.to_string(), (attachments, received));
+                            candidates
+                                .entry(supplier.to_string())
+                                .or_default()
+                                .push(Mail {
+                                    uid: fetch.uid,
+                                    received,
+                                    files: attachments,
+                                });
                         }
                     }
                 }
             }
         }
         session.logout()?;
-        Ok(m)
+        Ok(self.select(candidates))
+    }
+    /// Выбирает письма для загрузки: самое новое письмо поставщика, а для поставщиков,
+    /// которые делят остатки на несколько писем, все письма в окне до самого нового.
+    /// Остальные письма запоминаются как устаревшие.
+    fn select(&mut self, candidates: HashMap<String, Vec<Mail>>) -> FetchMap {
+        let mut m = HashMap::new();
+        for (supplier, mut mails) in candidates {
+            // При равных датах решает UID, чтобы выбор не зависел от порядка ответа сервера
+            mails.sort_by_key(|mail| (mail.received, mail.uid));
+            let Some(newest) = mails.last().map(|mail| mail.received) else {
+                continue;
+            };
+            let first_applied = match self.config.split_window(&supplier) {
+                Some(window) => mails.partition_point(|mail| mail.received < newest - window),
+                None => mails.len() - 1,
+            };
+            let applied = mails.split_off(first_applied);
+            if !mails.is_empty() {
+                info!(
+                    "{supplier}: писем {}, применяю письма от {}, устаревших {}",
+                    mails.len() + applied.len(),
+                    newest.format("%d.%m.%Y %H:%M"),
+                    mails.len()
+                );
+                self.superseded.insert(supplier.clone(), mails);
+            }
+            self.pending.insert(
+                supplier.clone(),
+                applied.iter().filter_map(|mail| mail.uid).collect(),
+            );
+            let files = applied.into_iter().flat_map(|mail| mail.files).collect();
+            m.insert(supplier, (files, newest));
+        }
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(split_suppliers: &[&str]) -> MailClient {
+        MailClient::new(&MailConfig {
+            host: String::new(),
+            user: String::new(),
+            pass: String::new(),
+            idle: false,
+            actions: MailActions::default(),
+            supplier_actions: HashMap::new(),
+            split_suppliers: split_suppliers.iter().map(|s| s.to_string()).collect(),
+            split_window: chrono::Duration::minutes(60),
+        })
+    }
+
+    /// Письмо с одним файлом, полученное через `minutes` минут после начала дня
+    fn mail(uid: u32, minutes: i64) -> Mail {
+        Mail {
+            uid: Some(uid),
+            received: Utc.with_ymd_and_hms(2025, 1, 15, 0, 0, 0).unwrap()
+                + chrono::Duration::minutes(minutes),
+            files: vec![SourceFile {
+                name: format!("{uid}.xlsx"),
+                content: vec![uid as u8],
+            }],
+        }
+    }
+
+    struct Case {
+        name: &'static str,
+        split: bool,
+        /// UID и минуты получения писем в порядке ответа сервера
+        mails: &'static [(u32, i64)],
+        applied: &'static [u32],
+        /// В порядке, в котором письма применяются, если новое не разобралось
+        fallback: &'static [u32],
+    }
+
+    const CASES: &[Case] = &[
+        Case {
+            name: "одно письмо",
+            split: false,
+            mails: &[(1, 0)],
+            applied: &[1],
+            fallback: &[],
+        },
+        Case {
+            name: "новое письмо вместо старых",
+            split: false,
+            mails: &[(3, 120), (1, 0), (2, 60)],
+            applied: &[3],
+            fallback: &[2, 1],
+        },
+        Case {
+            name: "дата важнее UID",
+            split: false,
+            mails: &[(5, 0), (4, 60)],
+            applied: &[4],
+            fallback: &[5],
+        },
+        Case {
+            name: "при равных датах больший UID",
+            split: false,
+            mails: &[(7, 30), (6, 30)],
+            applied: &[7],
+            fallback: &[6],
+        },
+        Case {
+            name: "письма в окне объединяются",
+            split: true,
+            mails: &[(1, 0), (2, 100), (3, 130), (4, 160)],
+            applied: &[2, 3, 4],
+            fallback: &[1],
+        },
+        Case {
+            name: "окно включает границу",
+            split: true,
+            mails: &[(1, 0), (2, 60)],
+            applied: &[1, 2],
+            fallback: &[],
+        },
+    ];
+
+    #[test]
+    fn select_mails() {
+        for case in CASES {
+            let supplier = "fenix";
+            let split: &[&str] = if case.split { &["fenix"] } else { &[] };
+            let mut client = client(split);
+            let mails = case.mails.iter().map(|&(uid, m)| mail(uid, m)).collect();
+            let fetched = client.select(HashMap::from([(supplier.to_string(), mails)]));
+            let (files, received) = &fetched[supplier];
+            let newest = case.mails.iter().map(|&(_, m)| m).max().unwrap();
+            assert_eq!(*received, mail(0, newest).received, "{}", case.name);
+            assert_eq!(client.uids_of(supplier), case.applied, "{}", case.name);
+            let names = case.applied.iter().map(|uid| format!("{uid}.xlsx"));
+            assert!(
+                files.iter().map(|f| f.name.clone()).eq(names),
+                "{}",
+                case.name
+            );
+            let mut superseded = client
+                .superseded()
+                .get(supplier)
+                .cloned()
+                .unwrap_or_default();
+            let mut fallback = Vec::new();
+            while let Some(mail) = MailClient::fallback(&mut superseded) {
+                fallback.push(mail.uid.unwrap());
+            }
+            assert_eq!(fallback, case.fallback, "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn select_suppliers_independently() {
+        let mut client = client(&[]);
+        let fetched = client.select(HashMap::from([
+            ("fox".to_string(), vec![mail(1, 0), mail(3, 60)]),
+            ("opus".to_string(), vec![mail(2, 30)]),
+        ]));
+        assert_eq!(fetched.len(), 2);
+        assert_eq!(client.uids_of("fox"), [3]);
+        assert_eq!(client.uids_of("opus"), [2]);
+        assert!(!client.superseded().contains_key("opus"));
+        assert!(client.uids_of("zefir").is_empty());
     }
 }
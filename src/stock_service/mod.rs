@@ -2,138 +2,530 @@ mod mail_client;
 mod parser;
 mod web_spider;
 
-use std::ops::Deref;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use crate::storage::StockStorage;
-use crate::{models::Stock, utils::pause};
-use mail_client::MailClient;
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
-use tracing::{error, info};
-use web_spider::Spider;
+use crate::config::{Config, MailConfig};
+use crate::health::Status;
+use crate::storage::{ImportStorage, StockStorage, SupplierStorage};
+use crate::{
+    models::{Import, MailSender, ParseReport, RawFile, Stock},
+    utils::{pause, sleep_or_cancel},
+};
+pub use mail_client::MailClient;
+use mail_client::{Mail, MailWait};
+pub use parser::{parse_files, Parsed};
+use tokio::sync::{
+    mpsc::{channel, Receiver, Sender},
+    oneshot,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use web_spider::{Registered, Spider};
 
-pub type FetchMap =
-    std::collections::HashMap<String, (Vec<Vec<u8>>, chrono::DateTime<chrono::Utc>)>;
+/// Пакетов остатков поставщиков в очереди на сохранение
+const SUPPLIERS_BUFFER: usize = 2;
+/// Пауза перед повторным подключением к почте после ошибки
+const MAIL_RETRY_SECS: u64 = 5 * 60;
+/// Ошибка загрузки для писем, вместо которых применено более новое
+const NOT_APPLIED: &str = "Не применено: есть более новое письмо";
+
+/// Остатки одного поставщика, которые сохраняются одной транзакцией
+struct Batch {
+    items: Vec<Stock>,
+    saved: oneshot::Sender<crate::Result<(u64, u64)>>,
+}
+
+/// Итог загрузки остатков одного поставщика
+struct Outcome {
+    supplier: String,
+    items: usize,
+    error: Option<String>,
+    report: Option<ParseReport>,
+}
+
+impl Outcome {
+    fn failed(supplier: String, error: impl Into<String>) -> Self {
+        Self {
+            supplier,
+            items: 0,
+            error: Some(error.into()),
+            report: None,
+        }
+    }
+    fn with_report(mut self, report: ParseReport) -> Self {
+        self.report = Some(report);
+        self
+    }
+    fn into_import(self, source: &str, mail_uids: Vec<u32>, mail_action: Option<String>) -> Import {
+        Import {
+            id: uuid::Uuid::new_v4(),
+            supplier: self.supplier,
+            source: source.to_string(),
+            items: self.items as i64,
+            error: self.error,
+            mail_uids: mail_uids.into_iter().map(i64::from).collect(),
+            mail_action,
+            superseded_by: None,
+            report: self.report.map(sqlx::types::Json),
+            created: chrono::Utc::now(),
+        }
+    }
+}
+
+/// Файл остатков, полученный из почты или из сети
+#[derive(Clone, Debug, Default)]
+pub struct SourceFile {
+    pub name: String,
+    pub content: Vec<u8>,
+}
+
+pub type FetchMap = HashMap<String, (Vec<SourceFile>, chrono::DateTime<chrono::Utc>)>;
 
 pub struct Stocker {
-    mail_client: Arc<MailClient>,
+    mail_client: Option<MailClient>,
     spider: Arc<Spider>,
     stock_storage: Arc<StockStorage>,
+    import_storage: Arc<ImportStorage>,
+    supplier_storage: Arc<SupplierStorage>,
+    status: Arc<Status>,
+    shutdown: CancellationToken,
 }
 impl Stocker {
     pub fn new(
-        // secrets: shuttle_runtime::SecretStore,
+        config: &Config,
         stock_storage: Arc<StockStorage>,
+        import_storage: Arc<ImportStorage>,
+        supplier_storage: Arc<SupplierStorage>,
+        status: Arc<Status>,
+        shutdown: CancellationToken,
     ) -> Arc<Self> {
-        let ort_user = std::env::var("ORTGRAPH_USERNAME")
-            .expect("не нашла ORTGRAPH_USER в Secrets.toml");
-        let ort_pass = std::env::var("ORTGRAPH_PASSWORD")
-            .expect("не нашла ORTGRAPH_PASSWORD в Secrets.toml");
-        let mail_host = std::env::var("MAIL_HOST")
-            .expect("не нашла MAIL_HOST в Secrets.toml");
-        let mail_user = std::env::var("MAIL_USER")
-            .expect("не нашла MAIL_USER в Secrets.toml");
-        let mail_pass = std::env::var("MAIL_PASS")
-            .expect("не нашла MAIL_PASS в Secrets.toml");
-        let mail_client = Arc::new(
-            MailClient::new(mail_user, mail_pass, mail_host).expect("Error init mail_client"),
-        );
-        let spider = Arc::new(Spider::new(ort_user, ort_pass).expect("Error init spider"));
+        let mail_client = config.mail.as_ref().map(MailClient::new);
+        let spider = Arc::new(Spider::new(config).expect("Error init spider"));
         Arc::new(Self {
             mail_client,
             spider,
             stock_storage,
+            import_storage,
+            supplier_storage,
+            status,
+            shutdown,
         })
     }
+    /// Запускает источники и сохраняет остатки. При остановке сервиса источники
+    /// завершают текущий цикл, а уже полученные остатки сохраняются до выхода.
+    ///
+    /// Источники отдают остатки пакетами по поставщикам в общий ограниченный канал:
+    /// пока сохранение не успевает, разбор следующих файлов ждет.
     pub async fn run(self: Arc<Self>) -> anyhow::Result<()> {
-        let (mail_sender, mail_receiver) = unbounded_channel();
-        tokio::spawn(mail_generator(
-            mail_sender,
-            self.clone().mail_client.clone(),
-        ));
-        let (web_sender, web_receiver) = unbounded_channel();
-        tokio::spawn(web_generator(web_sender, self.clone().spider.clone()));
-        let channels = vec![mail_receiver, web_receiver];
-        self.clone().saver(channels).await?;
+        let (tx, rx) = channel(SUPPLIERS_BUFFER);
+        if let Some(mail_client) = self.mail_client.clone() {
+            tokio::spawn(mail_generator(
+                tx.clone(),
+                mail_client,
+                self.import_storage.clone(),
+                self.supplier_storage.clone(),
+                self.status.clone(),
+                self.shutdown.clone(),
+            ));
+        } else {
+            info!("Почта не настроена, остатки из почты не загружаются");
+        }
+        if self.spider.is_enabled() {
+            for registered in self.spider.sources() {
+                tokio::spawn(web_generator(
+                    tx.clone(),
+                    self.spider.clone(),
+                    registered.clone(),
+                    self.import_storage.clone(),
+                    self.supplier_storage.clone(),
+                    self.status.clone(),
+                    self.shutdown.clone(),
+                ));
+            }
+        } else {
+            info!("Источники в сети отключены, остатки из сети не загружаются");
+        }
+        drop(tx);
+        self.saver(rx).await;
         Ok(())
     }
-    async fn saver(
-        self: Arc<Self>,
-        channels: Vec<UnboundedReceiver<Vec<Stock>>>,
-    ) -> anyhow::Result<()> {
-        let (tx, mut rx) = unbounded_channel();
-        for mut receiver in channels {
-            let tx = tx.clone();
-            tokio::spawn(async move {
-                while let Some(s) = receiver.recv().await {
-                    if let Err(e) = tx.send(s) {
-                        error!("{e:?}");
-                    }
+    /// Сохраняет остатки каждого поставщика отдельной транзакцией. Ошибка одного
+    /// поставщика не мешает сохранять остальных.
+    async fn saver(&self, mut rx: Receiver<Batch>) {
+        while let Some(batch) = rx.recv().await {
+            let supplier = batch
+                .items
+                .first()
+                .map(|s| s.supplier.clone())
+                .unwrap_or_default();
+            let result = self.stock_storage.update(&batch.items).await;
+            match &result {
+                Ok((deleted, inserted)) => {
+                    self.status.job_success("import").await;
+                    info!("{supplier}: удалено {deleted}, добавлено {inserted} строк остатков");
                 }
-            });
+                Err(e) => {
+                    error!("Ошибка сохранения остатков {supplier}: {e:?}");
+                    self.status.job_failure("import", e).await;
+                }
+            }
+            // Источник мог не дождаться результата, например при остановке
+            let _ = batch.saved.send(result);
         }
-        while let Some(s) = rx.recv().await {
-            let (deleted, inserted) = self.clone().stock_storage.clone().update(&s).await?;
-            info!("Удалено {deleted}, добавлено {inserted} строк остатков");
+    }
+}
+
+/// Получает почту один раз, начиная с последних 200 писем, от отправителей
+/// `senders` из справочника поставщиков. Письма не отмечаются
+pub async fn fetch_mail(
+    config: &MailConfig,
+    senders: HashMap<String, MailSender>,
+) -> crate::Result<FetchMap> {
+    let mut client = MailClient::new(config);
+    client.set_senders(senders);
+    client.fetch().await
+}
+
+/// Скачивает остатки включенных источников в сети один раз, результат по
+/// каждому источнику
+pub async fn scrape(
+    config: &Config,
+) -> crate::Result<Vec<(&'static str, crate::Result<FetchMap>)>> {
+    Ok(Spider::new(config)?.get_web().await)
+}
+
+/// Повторно разбирает сохраненный файл вместе с остальными файлами того же
+/// получения: поставщики, которые присылают остатки несколькими файлами, иначе
+/// потеряли бы остатки из других файлов. Если это не пробный запуск, остатки
+/// поставщика заменяются остатками из файлов и загрузка записывается с
+/// источником `reprocess`. Возвращает разобранные файлы и остатки.
+pub async fn reprocess(
+    file: &RawFile,
+    stock_storage: &StockStorage,
+    import_storage: &ImportStorage,
+    dry_run: bool,
+) -> crate::Result<(Vec<RawFile>, Parsed)> {
+    let batch = import_storage.batch(&file.supplier, file.received).await?;
+    let mut files = Vec::new();
+    for raw in &batch {
+        files.push(SourceFile {
+            name: raw.filename.clone(),
+            content: import_storage.content(raw).await?,
+        });
+    }
+    let parsed = parser::parse_files(&file.supplier, files, file.received).await?;
+    if dry_run || parsed.items.is_empty() {
+        return Ok((batch, parsed));
+    }
+    let result = stock_storage.update(&parsed.items).await;
+    let outcome = match &result {
+        Ok(_) => Outcome {
+            supplier: file.supplier.clone(),
+            items: parsed.items.len(),
+            error: None,
+            report: None,
+        },
+        Err(e) => Outcome::failed(file.supplier.clone(), format!("{e:?}")),
+    };
+    record_import(
+        import_storage,
+        outcome
+            .with_report(parsed.report.clone())
+            .into_import("reprocess", Vec::new(), None),
+    )
+    .await;
+    result?;
+    Ok((batch, parsed))
+}
+
+/// Сохраняет полученные файлы в архив, ошибка архива не мешает загрузке остатков
+async fn archive(storage: &ImportStorage, fetches: &FetchMap, source: &str) {
+    for (supplier, (files, received)) in fetches {
+        archive_files(storage, supplier, source, files, *received).await;
+    }
+}
+
+async fn archive_files(
+    storage: &ImportStorage,
+    supplier: &str,
+    source: &str,
+    files: &[SourceFile],
+    received: chrono::DateTime<chrono::Utc>,
+) {
+    for file in files {
+        if let Err(e) = storage
+            .archive(supplier, source, &file.name, &file.content, received)
+            .await
+        {
+            error!("Ошибка сохранения файла {} в архив: {e:?}", file.name);
+        }
+    }
+}
+
+/// Записывает итог загрузки, ошибка записи не останавливает загрузку остатков
+async fn record_import(storage: &ImportStorage, import: Import) {
+    if let Err(e) = storage.add(&import).await {
+        error!("Ошибка записи загрузки {}: {e:?}", import.supplier);
+    }
+}
+
+/// Отмечает письма, ошибка отметки записывается в загрузку вместо действия
+async fn mark_mail(
+    client: &MailClient,
+    supplier: &str,
+    uids: Vec<u32>,
+    success: bool,
+) -> Option<String> {
+    match client.mark(supplier, uids, success).await {
+        Ok(action) => action,
+        Err(e) => {
+            warn!("Не получилось отметить письма {supplier}: {e:?}");
+            Some(format!("Ошибка: {e}"))
         }
-        Ok(())
     }
 }
-async fn mail_generator(tx: UnboundedSender<Vec<Stock>>, mc: Arc<MailClient>) {
-    loop {
-        let mut client = mc.clone().deref().clone();
-        match client.fetch() {
+
+/// Обрабатывает письма поставщика, вместо которых применены более новые. Если
+/// новое письмо не разобралось, по очереди от новых к старым применяются старые
+/// письма, пока одно не разберется. Оставшиеся старые письма записываются как
+/// непримененные и отмечаются как загруженные, только если остатки поставщика
+/// применены; иначе они остаются в ящике без отметок. Возвращает число строк
+/// остатков из старых писем.
+async fn apply_superseded(
+    client: &MailClient,
+    supplier: &str,
+    mut mails: Vec<Mail>,
+    mut applied: Option<(uuid::Uuid, bool)>,
+    tx: &Sender<Batch>,
+    import_storage: &ImportStorage,
+) -> usize {
+    for mail in &mails {
+        archive_files(import_storage, supplier, "mail", &mail.files, mail.received).await;
+    }
+    let mut quantity = 0;
+    while applied.is_some_and(|(_, success)| !success) {
+        let Some(mail) = MailClient::fallback(&mut mails) else {
+            break;
+        };
+        info!(
+            "{supplier}: новое письмо не разобралось, применяю письмо от {}",
+            mail.received.format("%d.%m.%Y %H:%M")
+        );
+        let uids = mail.uid.into_iter().collect::<Vec<_>>();
+        let fetches = HashMap::from([(supplier.to_string(), (mail.files, mail.received))]);
+        for outcome in parser::parse(fetches, tx).await {
+            quantity += outcome.items;
+            let success = outcome.error.is_none();
+            let mail_action = mark_mail(client, supplier, uids.clone(), success).await;
+            let import = outcome.into_import("mail", uids.clone(), mail_action);
+            applied = Some((import.id, success));
+            record_import(import_storage, import).await;
+        }
+    }
+    if mails.is_empty() {
+        return quantity;
+    }
+    let uids = mails.iter().filter_map(|mail| mail.uid).collect::<Vec<_>>();
+    let (superseded_by, mail_action) = match applied {
+        Some((id, true)) => (
+            Some(id),
+            mark_mail(client, supplier, uids.clone(), true).await,
+        ),
+        _ => {
+            warn!("{supplier}: ни одно письмо не разобралось, старые письма оставлены без отметок");
+            (None, None)
+        }
+    };
+    let outcome = Outcome::failed(supplier.to_string(), NOT_APPLIED);
+    let mut import = outcome.into_import("mail", uids, mail_action);
+    import.superseded_by = superseded_by;
+    record_import(import_storage, import).await;
+    quantity
+}
+
+/// Забирает почту сразу после прихода писем, если сервер умеет IDLE, иначе раз в час.
+/// После ошибок соединения ожидание переподключается через `MAIL_RETRY_SECS`.
+async fn mail_generator(
+    tx: Sender<Batch>,
+    mut client: MailClient,
+    import_storage: Arc<ImportStorage>,
+    supplier_storage: Arc<SupplierStorage>,
+    status: Arc<Status>,
+    shutdown: CancellationToken,
+) {
+    let mut idle = client.idle_enabled();
+    while !shutdown.is_cancelled() {
+        // Справочник перечитывается перед каждой проверкой, чтобы новые адреса и
+        // отключение поставщика действовали без перезапуска
+        // Без отправителей письма поставщиков были бы пропущены: ящик все равно
+        // считается прочитанным до последнего письма
+        match supplier_storage.senders().await {
+            Ok(senders) => client.set_senders(senders),
+            Err(e) => {
+                error!("Не получилось прочитать отправителей из справочника поставщиков: {e:?}");
+                status.job_failure("mail", e).await;
+                if sleep_or_cancel(MAIL_RETRY_SECS, &shutdown).await {
+                    break;
+                }
+                continue;
+            }
+        }
+        // Чтение справочника могло застать остановку: начатое получение почты не
+        // прерывается и может не успеть до принудительной остановки
+        if shutdown.is_cancelled() {
+            break;
+        }
+        let fetched = match client.fetch().await {
             Ok(mails) => {
-                let items = parser::parse(mails).await;
-                let quantity = items.len();
-                if !items.is_empty() {
-                    if tx.send(items).is_err() {
-                        error!(
-                        "Ошибка при отправке почтовых вложений в канал, попробую еще раз через час"
-                    );
-                    } else {
-                        info!("Получено {quantity} строк остатков из почты, пауза на 1 час");
-                    }
+                status.job_success("mail").await;
+                archive(&import_storage, &mails, "mail").await;
+                let mut applied = HashMap::new();
+                let mut quantity = 0;
+                for outcome in parser::parse(mails, &tx).await {
+                    quantity += outcome.items;
+                    let supplier = outcome.supplier.clone();
+                    let uids = client.uids_of(&supplier);
+                    let success = outcome.error.is_none();
+                    let mail_action = mark_mail(&client, &supplier, uids.clone(), success).await;
+                    let import = outcome.into_import("mail", uids, mail_action);
+                    applied.insert(supplier, (import.id, success));
+                    record_import(&import_storage, import).await;
+                }
+                for (supplier, mails) in client.superseded().clone() {
+                    quantity += apply_superseded(
+                        &client,
+                        &supplier,
+                        mails,
+                        applied.get(&supplier).copied(),
+                        &tx,
+                        &import_storage,
+                    )
+                    .await;
+                }
+                if quantity > 0 {
+                    info!("Получено {quantity} строк остатков из почты");
                 } else {
-                    info!("Нет новых остатков в почте, пауза на 1 час");
+                    info!("Нет новых остатков в почте");
                 }
+                true
             }
             Err(e) => {
-                error!("Ошибка получения почты:\n{e:?}\n\nПопробую еще раз через час");
+                error!("Ошибка получения почты:\n{e:?}");
+                status.job_failure("mail", e).await;
+                false
             }
+        };
+        let stopped = if !idle {
+            info!("Следующая проверка почты через 1 час");
+            pause(1, &shutdown).await
+        } else if !fetched {
+            sleep_or_cancel(MAIL_RETRY_SECS, &shutdown).await
+        } else {
+            tokio::select! {
+                result = client.wait() => {
+                    match result {
+                        Ok(MailWait::Changed) => info!("В почтовом ящике новые письма"),
+                        Ok(MailWait::TimedOut) => {}
+                        Ok(MailWait::Unsupported) => {
+                            warn!("Почтовый сервер не поддерживает IDLE, проверяю почту раз в час");
+                            idle = false;
+                        }
+                        Err(e) => {
+                            warn!("Ошибка ожидания писем, переподключусь: {e:?}");
+                            if sleep_or_cancel(MAIL_RETRY_SECS, &shutdown).await {
+                                break;
+                            }
+                        }
+                    }
+                    false
+                }
+                _ = shutdown.cancelled() => true,
+            }
+        };
+        if stopped {
+            break;
+        }
+    }
+    info!("Получение почты остановлено");
+}
+
+/// Отключен ли поставщик в справочнике. Если справочник недоступен, поставщик
+/// считается включенным
+async fn is_disabled(storage: &SupplierStorage, supplier: &str) -> bool {
+    match storage.disabled().await {
+        Ok(disabled) => disabled.iter().any(|s| s == supplier),
+        Err(e) => {
+            error!("Не получилось прочитать справочник поставщиков: {e:?}");
+            false
         }
-        pause(1).await;
     }
 }
 
-async fn web_generator(tx: UnboundedSender<Vec<Stock>>, spider: Arc<Spider>) {
-    loop {
-        match spider.get_web().await {
-            Ok(f) => {
-                let webs = parser::parse(f).await;
-                let quantity = webs.len();
-                if !webs.is_empty() {
-                    if tx.send(webs).is_err() {
-                        error!(
-                            "Ошибка при отправке веб остатков в канал, попробую еще раз через час"
+/// Скачивает остатки одного источника в сети по его расписанию. Ошибка источника
+/// записывается в загрузки и в `/health` под его именем и не задерживает другие
+/// источники.
+async fn web_generator(
+    tx: Sender<Batch>,
+    spider: Arc<Spider>,
+    registered: Registered,
+    import_storage: Arc<ImportStorage>,
+    supplier_storage: Arc<SupplierStorage>,
+    status: Arc<Status>,
+    shutdown: CancellationToken,
+) {
+    let source = registered.source.as_ref();
+    let supplier = source.supplier();
+    while !shutdown.is_cancelled() {
+        let hours = if is_disabled(&supplier_storage, supplier).await {
+            info!("{supplier}: поставщик отключен, остатки не загружаются");
+            registered.interval_hours
+        } else {
+            match spider.fetch(source).await {
+                Ok(f) => {
+                    archive(&import_storage, &f, "web").await;
+                    let mut quantity = 0;
+                    let mut failure = None;
+                    for outcome in parser::parse(f, &tx).await {
+                        quantity += outcome.items;
+                        failure = failure.or(outcome.error.clone());
+                        record_import(
+                            &import_storage,
+                            outcome.into_import("web", Vec::new(), None),
+                        )
+                        .await;
+                    }
+                    if quantity > 0 {
+                        info!(
+                            "{supplier}: получено {quantity} строк остатков из сети, следующая загрузка через {} ч",
+                            registered.interval_hours
                         );
-                        pause(1).await;
+                        status.check_result(supplier, Ok(())).await;
+                        registered.interval_hours
                     } else {
-                        info!("Получено {quantity} строк остатков из сети, пауза на 24 часа");
-                        pause(24).await;
+                        let e = failure.unwrap_or("Нет строк остатков".to_string());
+                        error!("{supplier}: остатки из сети не загружены: {e}, попробую еще раз через час");
+                        status.check_result(supplier, Err(e)).await;
+                        1
                     }
-                } else {
-                    error!("Пустой ответ сети на запрос остатков, попробую еще раз через час");
-                    pause(1).await;
+                }
+                Err(e) => {
+                    error!("{supplier}: ошибка получения остатков из сети: {e:?}, попробую еще раз через час");
+                    let outcome = Outcome::failed(supplier.to_string(), format!("{e:?}"));
+                    record_import(
+                        &import_storage,
+                        outcome.into_import("web", Vec::new(), None),
+                    )
+                    .await;
+                    status.check_result(supplier, Err(format!("{e:?}"))).await;
+                    1
                 }
             }
-            Err(e) => {
-                error!("Ошибка получения почты:\n{e:?}\n\nПопробую еще раз через час");
-                pause(1).await;
-            }
+        };
+        if pause(hours, &shutdown).await {
+            break;
         }
     }
+    info!("{supplier}: получение остатков из сети остановлено");
 }
 // async fn router(
 //     mut rx: UnboundedReceiver<Vec<Stock>>,
@@ -1,65 +1,79 @@
-use std::io::Cursor;
-
-use calamine::{open_workbook_auto_from_rs, Data, Range, Reader};
+use calamine::{Data, Range};
 use chrono::{DateTime, Utc};
-use tokio::sync::mpsc::UnboundedSender;
 use tracing::error;
 
 use crate::models::Stock;
 
-use super::clear_string;
+use super::{
+    clear_string, collect,
+    columns::{Column, ARRIVAL, INCOMING, RESERVE, STOCK},
+    file_error, not_a_number, quantities, read_sheets, rows_channel, spawn_sheet, Parsed, Rows,
+};
 
-pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Vec<Stock> {
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Parsed {
+    let (tx, rx) = rows_channel();
     tokio::spawn(async move {
-        for file in files {
-            let cursor = Cursor::new(file);
-            match open_workbook_auto_from_rs(cursor) {
-                Ok(mut wb) => {
-                    let sheets = wb.worksheets();
-                    for (_, table) in sheets {
-                        let tx = tx.clone();
-                        tokio::spawn(parse(table, received, tx));
+        for (index, file) in files.into_iter().enumerate() {
+            match read_sheets(file, false).await {
+                Ok(sheets) => {
+                    for sheet in sheets {
+                        spawn_sheet(&tx, index, sheet, move |table, rows| {
+                            parse(table, received, rows)
+                        })
+                        .await;
                     }
                 }
                 Err(e) => {
                     error!("Ошибка при чтении книги из вложений 'Интерьерные решения': {e:?}");
+                    file_error(&tx, &e).await;
                     continue;
                 }
             }
         }
     });
-    let mut result = Vec::new();
-    while let Some(pr) = rx.recv().await {
-        result.push(pr)
-    }
-    result
+    collect(rx).await
 }
 
-async fn parse(table: Range<Data>, received: DateTime<Utc>, tx: UnboundedSender<Stock>) {
-    for row in table.rows() {
-        if let Some(stock) = row
-            .get(5)
+const BRAND: Column = Column::required("Бренд", &["бренд", "производитель", "марка"]);
+const COLLECTION: Column = Column::required("Коллекция", &["коллекция", "серия", "модель"]);
+const COLOR: Column = Column::required("Цвет", &["цвет", "дизайн", "рисунок"]);
+const WIDTH: Column = Column::required("Ширина", &["ширина", "ширина м", "размер"]);
+
+fn parse(table: Range<Data>, received: DateTime<Utc>, rows: Rows) {
+    let Some(header) = rows.find_header(
+        &table,
+        &[
+            BRAND, COLLECTION, COLOR, WIDTH, STOCK, RESERVE, INCOMING, ARRIVAL,
+        ],
+    ) else {
+        return;
+    };
+    for (i, row) in table.rows().enumerate().skip(header.row + 1) {
+        let sent = if let Some(stock) = header
+            .get(row, &STOCK)
             .and_then(|d| d.to_string().trim().parse::<f64>().ok())
         {
-            let brand = row.first().map(|d| d.to_string()).unwrap_or_default();
-            let collection = row.get(1).map(|d| d.to_string()).unwrap_or_default();
-            let color = row.get(2).map(|d| d.to_string()).unwrap_or_default();
-            let width = row
-                .get(3)
-                .map(|d| d.to_string().replace(',', "."))
-                .unwrap_or_default();
+            let brand = header.text(row, &BRAND);
+            let collection = header.text(row, &COLLECTION);
+            let color = header.text(row, &COLOR);
+            let width = header.text(row, &WIDTH).replace(',', ".");
             let name = format!("{brand} {collection} {color} {width}");
-            let item = Stock {
+            rows.accept(Stock {
                 name: clear_string(&name),
-                stock,
                 supplier: "carpetland".to_string(),
                 updated: received,
                 id: uuid::Uuid::new_v4(),
-            };
-            if tx.send(item).is_err() {
-                error!("Ошибка при отправке строки из файла в канал...")
-            }
+                brand: header.value(row, &BRAND),
+                collection: header.value(row, &COLLECTION),
+                color: header.value(row, &COLOR),
+                width: width.trim().parse().ok(),
+                ..quantities(&header, row, &STOCK, stock)
+            })
+        } else {
+            rows.skip(i, row, not_a_number(row, &header, &STOCK))
+        };
+        if !sent {
+            return;
         }
     }
 }
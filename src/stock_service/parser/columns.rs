@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use calamine::{Data, Range};
+
+/// Сколько строк сверху листа просматривается в поисках шапки
+const HEADER_SEARCH_ROWS: usize = 50;
+/// Короче этого опечатки в заголовках не прощаются: «Цвет» и «Цена» различаются
+/// одной буквой
+const FUZZY_MIN_LEN: usize = 6;
+
+/// Колонка, которую парсер ищет в шапке листа по названию и синонимам.
+/// Синонимы пишутся строчными буквами и проверяются по порядку: первый
+/// совпавший важнее следующих.
+#[derive(Clone, Copy, Debug)]
+pub struct Column {
+    pub name: &'static str,
+    pub synonyms: &'static [&'static str],
+    pub required: bool,
+}
+
+impl Column {
+    pub const fn required(name: &'static str, synonyms: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            synonyms,
+            required: true,
+        }
+    }
+    pub const fn optional(name: &'static str, synonyms: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            synonyms,
+            required: false,
+        }
+    }
+}
+
+/// Наименование товара
+pub const NAME: Column = Column::required(
+    "Наименование",
+    &["наименование", "номенклатура", "товар", "название"],
+);
+/// Остаток: свободный, если заголовок из `FREE_STOCK`, иначе весь остаток на складе
+pub const STOCK: Column = Column::required(
+    "Остаток",
+    &[
+        "свободно",
+        "свободный остаток",
+        "остаток",
+        "остатки",
+        "доступно",
+        "в наличии",
+        "наличие",
+        "количество",
+        "кол-во",
+    ],
+);
+/// Заголовки колонки остатка, которые означают свободный остаток без резерва
+const FREE_STOCK: [&str; 3] = ["свободно", "свободный остаток", "доступно"];
+/// Зарезервированное количество
+pub const RESERVE: Column = Column::optional("Резерв", &["резерв", "в резерве", "зарезервировано"]);
+/// Ожидаемый приход
+pub const INCOMING: Column = Column::optional(
+    "В пути",
+    &[
+        "в пути",
+        "ожидается",
+        "ожидаемый приход",
+        "поступление",
+        "приход",
+    ],
+);
+/// Дата ожидаемого прихода
+pub const ARRIVAL: Column = Column::optional(
+    "Дата прихода",
+    &[
+        "дата прихода",
+        "дата поступления",
+        "ожидаемая дата",
+        "дата поставки",
+        "срок поступления",
+    ],
+);
+/// Артикул или код товара у поставщика
+pub const ARTICLE: Column = Column::optional("Артикул", &["артикул", "код товара", "код", "арт"]);
+/// Производитель или торговая марка
+pub const BRAND: Column = Column::optional(
+    "Бренд",
+    &["бренд", "производитель", "торговая марка", "марка"],
+);
+
+/// Шапка листа: номер ее строки и номера найденных колонок в таблице
+#[derive(Debug)]
+pub struct Header {
+    pub row: usize,
+    indices: HashMap<&'static str, usize>,
+    /// Синоним, с которым совпал заголовок колонки
+    synonyms: HashMap<&'static str, &'static str>,
+}
+
+impl Header {
+    /// Ищет строку шапки среди первых `HEADER_SEARCH_ROWS` строк: ту, в которой
+    /// нашлись все обязательные колонки и больше всего необязательных.
+    /// Колонки могут стоять в любом порядке, лишние колонки не мешают.
+    pub fn find(table: &Range<Data>, columns: &[Column]) -> Result<Header, String> {
+        let mut best: Option<Header> = None;
+        let mut closest: Vec<&str> = columns
+            .iter()
+            .filter(|c| c.required)
+            .map(|c| c.name)
+            .collect();
+        for (row, cells) in table.rows().take(HEADER_SEARCH_ROWS).enumerate() {
+            let (indices, synonyms) = match_columns(cells, columns);
+            let missing = columns
+                .iter()
+                .filter(|c| c.required && !indices.contains_key(c.name))
+                .map(|c| c.name)
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                if missing.len() < closest.len() {
+                    closest = missing;
+                }
+                continue;
+            }
+            if best
+                .as_ref()
+                .is_none_or(|b| indices.len() > b.indices.len())
+            {
+                best = Some(Header {
+                    row,
+                    indices,
+                    synonyms,
+                });
+            }
+        }
+        best.ok_or_else(|| format!("Не найдены колонки: {}", closest.join(", ")))
+    }
+    /// Номер колонки в таблице, если она нашлась
+    pub fn index(&self, column: &Column) -> Option<usize> {
+        self.indices.get(column.name).copied()
+    }
+    /// Заголовок колонки остатка говорит, что это свободный остаток без резерва
+    pub fn is_free(&self, column: &Column) -> bool {
+        self.synonyms
+            .get(column.name)
+            .is_some_and(|s| FREE_STOCK.contains(s))
+    }
+    /// Ячейка колонки в строке таблицы
+    pub fn get<'a>(&self, row: &'a [Data], column: &Column) -> Option<&'a Data> {
+        row.get(self.index(column)?)
+    }
+    /// Текст ячейки колонки или пустая строка
+    pub fn text(&self, row: &[Data], column: &Column) -> String {
+        self.get(row, column)
+            .map(|d| d.to_string())
+            .unwrap_or_default()
+    }
+    /// Текст ячейки колонки без пробелов по краям, если он не пустой
+    pub fn value(&self, row: &[Data], column: &Column) -> Option<String> {
+        Some(self.text(row, column).trim().to_string()).filter(|s| !s.is_empty())
+    }
+}
+
+/// Номера колонок, найденных в строке, и совпавшие синонимы. Каждая ячейка
+/// достается одной колонке: колонки с более точным совпадением выбирают первыми.
+fn match_columns(
+    cells: &[Data],
+    columns: &[Column],
+) -> (
+    HashMap<&'static str, usize>,
+    HashMap<&'static str, &'static str>,
+) {
+    let labels = cells
+        .iter()
+        .map(|d| match d {
+            Data::String(s) => normalize(s),
+            _ => String::new(),
+        })
+        .collect::<Vec<_>>();
+    let mut candidates = Vec::new();
+    for column in columns {
+        for (index, label) in labels.iter().enumerate() {
+            if let Some(score) = score(label, column.synonyms) {
+                candidates.push((score, column.name, index, column.synonyms[score.1]));
+            }
+        }
+    }
+    candidates.sort();
+    let mut indices = HashMap::new();
+    let mut synonyms = HashMap::new();
+    let mut taken = Vec::new();
+    for (_, name, index, synonym) in candidates {
+        if indices.contains_key(name) || taken.contains(&index) {
+            continue;
+        }
+        indices.insert(name, index);
+        synonyms.insert(name, synonym);
+        taken.push(index);
+    }
+    (indices, synonyms)
+}
+
+/// Насколько заголовок похож на синонимы колонки, меньше лучше: сначала
+/// точное совпадение, потом заголовок, начинающийся с синонима («Остаток, м2»),
+/// потом опечатка в одну букву. Внутри каждого вида важнее ранний синоним.
+fn score(label: &str, synonyms: &[&str]) -> Option<(usize, usize)> {
+    if label.is_empty() {
+        return None;
+    }
+    let exact = synonyms.iter().position(|s| label == *s);
+    let prefix = || {
+        synonyms.iter().position(|s| {
+            label
+                .strip_prefix(s)
+                .is_some_and(|rest| rest.starts_with(' '))
+        })
+    };
+    let fuzzy = || {
+        synonyms
+            .iter()
+            .position(|s| s.chars().count() >= FUZZY_MIN_LEN && levenshtein(label, s) <= 1)
+    };
+    exact
+        .map(|i| (0, i))
+        .or_else(|| prefix().map(|i| (1, i)))
+        .or_else(|| fuzzy().map(|i| (2, i)))
+}
+
+/// Строчные буквы без знаков препинания, «ё» как «е», пробелы схлопнуты
+fn normalize(label: &str) -> String {
+    label
+        .to_lowercase()
+        .replace('ё', "е")
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_labels() {
+        assert_eq!(normalize("  Остаток,\tм2 "), "остаток м2");
+        assert_eq!(normalize("Кол-во (шт.)"), "кол-во шт");
+        assert_eq!(normalize("Всё в пути"), "все в пути");
+        assert_eq!(normalize("…"), "");
+    }
+
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(levenshtein("остаток", "остаток"), 0);
+        assert_eq!(levenshtein("остатки", "остаток"), 2);
+        assert_eq!(levenshtein("цена", "цены"), 1);
+        assert_eq!(levenshtein("остатк", "остаток"), 1);
+        assert_eq!(levenshtein("цвет", "цена"), 3);
+        assert_eq!(levenshtein("", "резерв"), 6);
+    }
+
+    #[test]
+    fn score_prefers_exact_then_prefix_then_typo() {
+        let synonyms = STOCK.synonyms;
+        assert_eq!(score("остаток", synonyms), Some((0, 2)));
+        assert_eq!(score("остаток м2", synonyms), Some((1, 2)));
+        assert_eq!(score("остатк", synonyms), Some((2, 2)));
+        // Ранний синоним важнее: «свободный остаток» не считается «остатком»
+        assert_eq!(score("свободный остаток", synonyms), Some((0, 1)));
+        // Приставка должна кончаться пробелом, а не продолжаться словом
+        assert_eq!(score("остаточный", synonyms), None);
+        assert_eq!(score("", synonyms), None);
+    }
+
+    #[test]
+    fn score_ignores_typos_in_short_labels() {
+        // «Цена» и «Цены» отличаются буквой, но короткие заголовки сравниваются точно
+        assert_eq!(score("цены", &["цена"]), None);
+        assert_eq!(score("арт", ARTICLE.synonyms), Some((0, 3)));
+        assert_eq!(score("артикл", ARTICLE.synonyms), Some((2, 0)));
+    }
+
+    #[test]
+    fn header_matches_columns_in_any_order() {
+        let table = Range::from_sparse(vec![
+            calamine::Cell::new((0, 0), Data::String("Прайс".into())),
+            calamine::Cell::new((1, 0), Data::String("Остаток, м2".into())),
+            calamine::Cell::new((1, 1), Data::String("Цвет".into())),
+            calamine::Cell::new((1, 2), Data::String("Номенклатура".into())),
+        ]);
+        let header = Header::find(&table, &[NAME, STOCK, RESERVE]).unwrap();
+        assert_eq!(header.row, 1);
+        assert_eq!(header.index(&NAME), Some(2));
+        assert_eq!(header.index(&STOCK), Some(0));
+        assert_eq!(header.index(&RESERVE), None);
+        assert!(!header.is_free(&STOCK));
+        let missing = Header::find(&table, &[NAME, STOCK, ARTICLE, BRAND]).unwrap();
+        assert_eq!(missing.index(&BRAND), None);
+        let error = Header::find(&table, &[NAME, Column::required("Цена", &["цена"])]);
+        assert_eq!(error.unwrap_err(), "Не найдены колонки: Цена");
+    }
+}
@@ -1,70 +1,86 @@
-use std::io::Cursor;
-
-use calamine::{open_workbook_auto_from_rs, Data, DataType, Range, Reader};
+use calamine::{Data, Range};
 use chrono::{DateTime, Utc};
-use tokio::sync::mpsc::UnboundedSender;
 use tracing::error;
 
 use crate::models::Stock;
 
-use super::clear_string;
+use super::{
+    clear_string, collect,
+    columns::{Column, ARRIVAL, INCOMING, NAME, RESERVE},
+    file_error,
+    hierarchy::{classify, Groups, Kind},
+    not_a_number, number, quantities, read_sheets, rows_channel, spawn_sheet, Parsed, Rows,
+};
 
-pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Vec<Stock> {
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Parsed {
+    let (tx, rx) = rows_channel();
     tokio::spawn(async move {
-        for file in files {
-            let cursor = Cursor::new(file);
-            match open_workbook_auto_from_rs(cursor) {
-                Ok(mut wb) => {
-                    let sheets = wb.worksheets();
-                    for (_, table) in sheets {
-                        let tx = tx.clone();
-                        tokio::spawn(parse(table, received, tx));
+        for (index, file) in files.into_iter().enumerate() {
+            match read_sheets(file, false).await {
+                Ok(sheets) => {
+                    for sheet in sheets {
+                        spawn_sheet(&tx, index, sheet, move |table, rows| {
+                            parse(table, received, rows)
+                        })
+                        .await;
                     }
                 }
                 Err(e) => {
                     error!("Ошибка при открытии книги из вложений от 'Фэнси': {e:?}");
+                    file_error(&tx, &e).await;
                     continue;
                 }
             }
         }
     });
-    let mut result = Vec::new();
-    while let Some(pr) = rx.recv().await {
-        result.push(pr)
-    }
-    result
+    collect(rx).await
 }
 
-async fn parse(table: Range<Data>, received: DateTime<Utc>, tx: UnboundedSender<Stock>) {
-    let re = regex::Regex::new(r#"^([A-z]+)\s.+$"#).unwrap();
-    let mut name = String::new();
-    for row in table.rows() {
-        let temp_name = row.first().and_then(|d| d.get_string()).unwrap_or_default();
-        if re.is_match(temp_name) {
-            let second_name = row.get(4).and_then(|d| d.get_string()).unwrap_or_default();
-            name = format!("{temp_name} {second_name}");
-        } else if let Some(current) = row
-            .get(4)
-            .and_then(|d| d.to_string().trim().parse::<f64>().ok())
-        {
-            let reserved = row
-                .get(6)
-                .and_then(|d| d.to_string().trim().parse::<f64>().ok())
-                .unwrap_or_default();
-            let stock = current - reserved;
-            if !name.is_empty() {
-                let item = Stock {
-                    name: clear_string(&name),
-                    stock,
-                    supplier: "fancy".to_string(),
-                    updated: received,
-                    id: uuid::Uuid::new_v4(),
-                };
-                if tx.send(item).is_err() {
-                    error!("Ошибка отправки строки в канал...")
+/// Остаток вместе с резервом
+const TOTAL: Column = Column::required(
+    "Остаток",
+    &[
+        "остаток",
+        "остатки",
+        "в наличии",
+        "наличие",
+        "количество",
+        "кол-во",
+    ],
+);
+
+fn parse(table: Range<Data>, received: DateTime<Utc>, rows: Rows) {
+    let Some(header) = rows.find_header(&table, &[NAME, TOTAL, RESERVE, INCOMING, ARRIVAL]) else {
+        return;
+    };
+    let kinds = classify(&table, &header, &rows, &NAME, &TOTAL);
+    let mut groups = Groups::default();
+    for (i, row) in table.rows().enumerate().skip(header.row + 1) {
+        let sent = match kinds[i] {
+            Kind::Group(level) => {
+                // В строке группы в колонке остатка написано продолжение названия
+                let name = format!("{} {}", header.text(row, &NAME), header.text(row, &TOTAL));
+                groups.enter(level, name);
+                rows.header(i)
+            }
+            Kind::Item => {
+                // Свободный остаток это остаток за вычетом резерва
+                let current = header.get(row, &TOTAL).and_then(number).unwrap_or_default();
+                match groups.last() {
+                    Some(name) => rows.accept(Stock {
+                        name: clear_string(name),
+                        supplier: "fancy".to_string(),
+                        updated: received,
+                        id: uuid::Uuid::new_v4(),
+                        ..quantities(&header, row, &TOTAL, current)
+                    }),
+                    None => rows.skip(i, row, "Строка до первой группы товаров"),
                 }
             }
+            Kind::Other => rows.skip(i, row, not_a_number(row, &header, &TOTAL)),
+        };
+        if !sent {
+            return;
         }
     }
 }
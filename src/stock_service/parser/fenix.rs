@@ -1,58 +1,64 @@
-use std::io::Cursor;
-
-use calamine::{open_workbook_auto_from_rs, Data, Range, Reader};
+use calamine::{Data, Range};
 use chrono::{DateTime, Utc};
-use tokio::sync::mpsc::UnboundedSender;
 use tracing::error;
 
 use crate::models::Stock;
 
-use super::clear_string;
+use super::{
+    clear_string, collect,
+    columns::{ARRIVAL, ARTICLE, INCOMING, NAME, RESERVE, STOCK},
+    file_error, not_a_number, quantities, read_sheets, rows_channel, spawn_sheet, Parsed, Rows,
+};
 
-pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Vec<Stock> {
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Parsed {
+    let (tx, rx) = rows_channel();
     tokio::spawn(async move {
-        for file in files {
-            let cursor = Cursor::new(file);
-            match open_workbook_auto_from_rs(cursor) {
-                Ok(mut wb) => {
-                    let sheets = wb.worksheets();
-                    for (_, table) in sheets {
-                        let tx = tx.clone();
-                        tokio::spawn(parse(table, received, tx));
+        for (index, file) in files.into_iter().enumerate() {
+            match read_sheets(file, false).await {
+                Ok(sheets) => {
+                    for sheet in sheets {
+                        spawn_sheet(&tx, index, sheet, move |table, rows| {
+                            parse(table, received, rows)
+                        })
+                        .await;
                     }
                 }
                 Err(e) => {
                     error!("Ошибка при открытии книги из вложений от 'Феникс': {e:?}");
+                    file_error(&tx, &e).await;
                     continue;
                 }
             }
         }
     });
-    let mut result = Vec::new();
-    while let Some(pr) = rx.recv().await {
-        result.push(pr)
-    }
-    result
+    collect(rx).await
 }
 
-async fn parse(table: Range<Data>, received: DateTime<Utc>, tx: UnboundedSender<Stock>) {
-    for row in table.rows() {
-        if let Some(stock) = row
-            .last()
+fn parse(table: Range<Data>, received: DateTime<Utc>, rows: Rows) {
+    let Some(header) =
+        rows.find_header(&table, &[NAME, ARTICLE, STOCK, RESERVE, INCOMING, ARRIVAL])
+    else {
+        return;
+    };
+    for (i, row) in table.rows().enumerate().skip(header.row + 1) {
+        let sent = if let Some(stock) = header
+            .get(row, &STOCK)
             .and_then(|d| d.to_string().trim().parse::<f64>().ok())
         {
-            let name = row.first().map(|d| d.to_string()).unwrap_or_default();
-            let item = Stock {
+            let name = header.text(row, &NAME);
+            rows.accept(Stock {
                 name: clear_string(&name),
-                stock,
                 supplier: String::from("fenix"),
                 updated: received,
                 id: uuid::Uuid::new_v4(),
-            };
-            if tx.send(item).is_err() {
-                error!("Ошибка отправки строки в канал...")
-            }
+                article: header.value(row, &ARTICLE),
+                ..quantities(&header, row, &STOCK, stock)
+            })
+        } else {
+            rows.skip(i, row, not_a_number(row, &header, &STOCK))
+        };
+        if !sent {
+            return;
         }
     }
 }
@@ -0,0 +1,401 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+};
+
+use calamine::{open_workbook_auto_from_rs, Data, Range, Reader};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1251};
+
+use super::styles::{xlsx_row_styles, RowStyle};
+use crate::{AppError, Result};
+
+/// Вложенность архивов, глубже которой файлы не распаковываются
+const MAX_ARCHIVE_DEPTH: usize = 2;
+/// Размер одного распакованного файла, больше которого файл пропускается
+const MAX_UNPACKED_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Имя единственной таблицы CSV
+const CSV_TABLE: &str = "CSV";
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const OLE_MAGIC: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// Таблица листа или CSV с оформлением строк, если формат его хранит
+#[derive(Debug)]
+pub struct Sheet {
+    pub name: String,
+    pub table: Range<Data>,
+    /// Оформление строк по номеру строки листа с нуля, см. [`xlsx_row_styles`]
+    pub styles: HashMap<u32, RowStyle>,
+}
+
+impl Sheet {
+    fn new(name: String, table: Range<Data>) -> Self {
+        Self {
+            name,
+            table,
+            styles: HashMap::new(),
+        }
+    }
+}
+
+/// Формат файла, определенный по содержимому, а не по расширению
+#[derive(Debug, PartialEq, Eq)]
+enum Format {
+    /// xls, xlsx, xlsb или ods, читается calamine
+    Workbook,
+    /// Архив с файлами остатков
+    Zip,
+    Csv,
+}
+
+fn sniff(content: &[u8]) -> Format {
+    if content.starts_with(OLE_MAGIC) {
+        return Format::Workbook;
+    }
+    if content.starts_with(ZIP_MAGIC) {
+        // xlsx и ods тоже zip, отличаются служебными файлами внутри
+        let is_workbook = zip::ZipArchive::new(Cursor::new(content)).is_ok_and(|archive| {
+            archive
+                .file_names()
+                .any(|name| name == "[Content_Types].xml" || name == "mimetype")
+        });
+        return if is_workbook {
+            Format::Workbook
+        } else {
+            Format::Zip
+        };
+    }
+    if looks_like_text(content) {
+        Format::Csv
+    } else {
+        Format::Workbook
+    }
+}
+
+/// Таблицы с именами из файла остатков: книги читаются calamine, архивы
+/// распаковываются, CSV декодируется с определением кодировки. Из книг берется
+/// только первый лист, если `first_only`. Таблицы из архива называются
+/// `<файл>/<лист>`.
+pub fn tables(content: Vec<u8>, first_only: bool) -> Result<Vec<Sheet>> {
+    tables_at_depth(content, first_only, 0)
+}
+
+fn tables_at_depth(content: Vec<u8>, first_only: bool, depth: usize) -> Result<Vec<Sheet>> {
+    match sniff(&content) {
+        Format::Workbook => workbook(content, first_only),
+        Format::Csv => Ok(vec![Sheet::new(
+            CSV_TABLE.to_string(),
+            csv(&decode(&content)),
+        )]),
+        Format::Zip if depth >= MAX_ARCHIVE_DEPTH => {
+            Err(AppError::Custom("Слишком глубоко вложенные архивы".into()))
+        }
+        Format::Zip => {
+            let mut tables = Vec::new();
+            let mut errors = Vec::new();
+            for (name, file) in unzip(content)? {
+                match tables_at_depth(file, first_only, depth + 1) {
+                    Ok(t) => tables.extend(t.into_iter().map(|sheet| Sheet {
+                        name: format!("{name}/{}", sheet.name),
+                        ..sheet
+                    })),
+                    Err(e) => errors.push(format!("{name}: {e:?}")),
+                }
+            }
+            if tables.is_empty() && !errors.is_empty() {
+                return Err(AppError::Custom(errors.join("; ")));
+            }
+            Ok(tables)
+        }
+    }
+}
+
+fn workbook(content: Vec<u8>, first_only: bool) -> Result<Vec<Sheet>> {
+    let mut styles = xlsx_row_styles(&content);
+    let mut wb = open_workbook_auto_from_rs(Cursor::new(content))
+        .map_err(|e| AppError::Custom(format!("{e:?}")))?;
+    let sheets = if first_only {
+        let name = wb.sheet_names().first().cloned().unwrap_or_default();
+        wb.worksheet_range_at(0)
+            .and_then(|r| r.ok())
+            .map(|r| (name, r))
+            .into_iter()
+            .collect()
+    } else {
+        wb.worksheets()
+    };
+    Ok(sheets
+        .into_iter()
+        .map(|(name, table)| Sheet {
+            styles: styles.remove(&name).unwrap_or_default(),
+            name,
+            table,
+        })
+        .collect())
+}
+
+/// Файлы архива без каталогов и служебных файлов macOS
+fn unzip(content: Vec<u8>) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(content))
+        .map_err(|e| AppError::Custom(format!("{e:?}")))?;
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| AppError::Custom(format!("{e:?}")))?;
+        let name = file.name().to_string();
+        if file.is_dir() || name.starts_with("__MACOSX/") {
+            continue;
+        }
+        if file.size() > MAX_UNPACKED_SIZE {
+            tracing::warn!("Файл {name} в архиве больше {MAX_UNPACKED_SIZE} байт, пропускаю");
+            continue;
+        }
+        let mut buffer = Vec::new();
+        file.by_ref()
+            .take(MAX_UNPACKED_SIZE)
+            .read_to_end(&mut buffer)
+            .map_err(|e| AppError::Custom(format!("{e:?}")))?;
+        files.push((name, buffer));
+    }
+    Ok(files)
+}
+
+/// Текст без нулевых байтов, кроме UTF-16 с BOM
+fn looks_like_text(content: &[u8]) -> bool {
+    if Encoding::for_bom(content).is_some() {
+        return true;
+    }
+    let head = &content[..content.len().min(4096)];
+    !head.is_empty() && !head.contains(&0)
+}
+
+/// Кодировка по BOM, иначе UTF-8, если текст корректен, иначе Windows-1251:
+/// в ней выгружают CSV российские учетные программы
+fn decode(content: &[u8]) -> String {
+    let encoding = match Encoding::for_bom(content) {
+        Some((encoding, _)) => encoding,
+        None if std::str::from_utf8(content).is_ok() => UTF_8,
+        None => WINDOWS_1251,
+    };
+    let (text, _, _) = encoding.decode(content);
+    text.into_owned()
+}
+
+/// Разделитель, который чаще всего встречается в первой строке
+fn delimiter(text: &str) -> char {
+    let first_line = text
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or_default();
+    [';', ',', '\t']
+        .into_iter()
+        .max_by_key(|d| first_line.matches(*d).count())
+        .filter(|d| first_line.contains(*d))
+        .unwrap_or(';')
+}
+
+fn csv(text: &str) -> Range<Data> {
+    let delimiter = delimiter(text);
+    let rows = csv_rows(text, delimiter);
+    let width = rows.iter().map(Vec::len).max().unwrap_or_default();
+    if rows.is_empty() || width == 0 {
+        return Range::empty();
+    }
+    let mut range = Range::new((0, 0), (rows.len() as u32 - 1, width as u32 - 1));
+    for (r, row) in rows.into_iter().enumerate() {
+        for (c, value) in row.into_iter().enumerate() {
+            range.set_value((r as u32, c as u32), cell(value));
+        }
+    }
+    range
+}
+
+/// Строки CSV по RFC 4180: поля в кавычках могут содержать разделитель,
+/// переводы строк и удвоенные кавычки
+fn csv_rows(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if c == delimiter && !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Числа в ячейках CSV становятся числами, как в книгах: `1 234,5` это 1234.5.
+/// Значения с ведущим нулем вроде артикулов `0012` остаются строками.
+fn cell(value: String) -> Data {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Data::Empty;
+    }
+    let number = trimmed.replace([' ', '\u{a0}'], "").replace(',', ".");
+    let digits = number.strip_prefix('-').unwrap_or(&number);
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    let numeric = !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1
+        && !digits.starts_with('.')
+        && !digits.ends_with('.');
+    match number.parse::<f64>() {
+        Ok(n) if numeric && !leading_zero => Data::Float(n),
+        _ => Data::String(trimmed.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Архив из файлов без сжатия
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, content) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn cp1251(text: &str) -> Vec<u8> {
+        WINDOWS_1251.encode(text).0.into_owned()
+    }
+
+    #[test]
+    fn sniff_by_content() {
+        assert_eq!(
+            sniff(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1rest"),
+            Format::Workbook
+        );
+        assert_eq!(sniff(&zip(&[("stock.csv", b"a;b")])), Format::Zip);
+        let xlsx = zip(&[
+            ("[Content_Types].xml", b"<Types/>"),
+            ("xl/workbook.xml", b""),
+        ]);
+        assert_eq!(sniff(&xlsx), Format::Workbook);
+        assert_eq!(sniff(&cp1251("Наименование;Остаток")), Format::Csv);
+        assert_eq!(sniff(b"\xFF\xFEa\0;\0b\0"), Format::Csv);
+        assert_eq!(sniff(b"bin\0ary"), Format::Workbook);
+        assert_eq!(sniff(b""), Format::Workbook);
+    }
+
+    #[test]
+    fn decode_encodings() {
+        assert_eq!(decode(&cp1251("Ковер;Остаток")), "Ковер;Остаток");
+        assert_eq!(decode("Ковер;Остаток".as_bytes()), "Ковер;Остаток");
+        assert_eq!(decode(b"\xEF\xBB\xBFa;b"), "a;b");
+    }
+
+    #[test]
+    fn delimiter_from_first_line() {
+        assert_eq!(delimiter("a;b;c\n1,5;2,5;3"), ';');
+        assert_eq!(delimiter("a,b,c\n1;2"), ',');
+        assert_eq!(delimiter("a\tb\tc"), '\t');
+        assert_eq!(delimiter("\n  \na,b"), ',');
+        assert_eq!(delimiter("Остатки"), ';');
+    }
+
+    #[test]
+    fn csv_rows_quoted_fields() {
+        let text =
+            "Наименование;Остаток\r\n\"Ковер \"\"Феникс\"\"; круг\";1,5\r\n\"Две\nстроки\";2";
+        assert_eq!(
+            csv_rows(text, ';'),
+            [
+                vec!["Наименование", "Остаток"],
+                vec!["Ковер \"Феникс\"; круг", "1,5"],
+                vec!["Две\nстроки", "2"],
+            ]
+        );
+        assert_eq!(
+            csv_rows("a;;b\n;\n", ';'),
+            [vec!["a", "", "b"], vec!["", ""]]
+        );
+        assert!(csv_rows("", ';').is_empty());
+    }
+
+    #[test]
+    fn cell_numbers() {
+        assert_eq!(cell("1 234,5".into()), Data::Float(1234.5));
+        assert_eq!(cell("1\u{a0}234".into()), Data::Float(1234.0));
+        assert_eq!(cell(" -3 ".into()), Data::Float(-3.0));
+        assert_eq!(cell("0,5".into()), Data::Float(0.5));
+        assert_eq!(cell("0".into()), Data::Float(0.0));
+        assert_eq!(cell("0012".into()), Data::String("0012".into()));
+        assert_eq!(cell("1.2.3".into()), Data::String("1.2.3".into()));
+        assert_eq!(cell(",5".into()), Data::String(",5".into()));
+        assert_eq!(cell("12 шт".into()), Data::String("12 шт".into()));
+        assert_eq!(cell("  ".into()), Data::Empty);
+    }
+
+    #[test]
+    fn csv_table_in_windows_1251() {
+        let sheets = tables(cp1251("Артикул;Остаток\n0012;1 234,5\n"), false).unwrap();
+        assert_eq!(sheets.len(), 1);
+        assert_eq!(sheets[0].name, CSV_TABLE);
+        let table = &sheets[0].table;
+        assert_eq!(
+            table.get_value((0, 1)),
+            Some(&Data::String("Остаток".into()))
+        );
+        assert_eq!(table.get_value((1, 0)), Some(&Data::String("0012".into())));
+        assert_eq!(table.get_value((1, 1)), Some(&Data::Float(1234.5)));
+    }
+
+    #[test]
+    fn nested_archives() {
+        let inner = zip(&[("stock.csv", b"a;1"), ("__MACOSX/._stock.csv", b"\0")]);
+        let outer = zip(&[("inner.zip", &inner), ("readme.txt", b"x;2")]);
+        let mut names = tables(outer, false)
+            .unwrap()
+            .into_iter()
+            .map(|sheet| sheet.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["inner.zip/stock.csv/CSV", "readme.txt/CSV"]);
+    }
+
+    #[test]
+    fn archive_depth_limit() {
+        let mut archive = zip(&[("stock.csv", b"a;1")]);
+        for depth in 0..MAX_ARCHIVE_DEPTH {
+            archive = zip(&[(&format!("{depth}.zip"), &archive)]);
+        }
+        let error = tables(archive, false).unwrap_err();
+        assert!(
+            format!("{error:?}").contains("Слишком глубоко вложенные архивы"),
+            "{error:?}"
+        );
+        let mut archive = zip(&[("stock.csv", b"a;1")]);
+        for depth in 1..MAX_ARCHIVE_DEPTH {
+            archive = zip(&[(&format!("{depth}.zip"), &archive)]);
+        }
+        assert_eq!(tables(archive, false).unwrap().len(), 1);
+    }
+}
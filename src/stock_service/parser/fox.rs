@@ -1,61 +1,74 @@
-use std::io::Cursor;
-
-use calamine::{open_workbook_auto_from_rs, Data, DataType, Range, Reader};
+use calamine::{Data, Range};
 use chrono::{DateTime, Utc};
-use tokio::sync::mpsc::UnboundedSender;
 use tracing::error;
 
 use crate::models::Stock;
 
-use super::clear_string;
+use super::{
+    clear_string, collect,
+    columns::{ARRIVAL, ARTICLE, INCOMING, NAME, RESERVE, STOCK},
+    file_error,
+    hierarchy::{classify, Groups, Kind},
+    not_a_number, number, quantities, read_sheets, rows_channel, spawn_sheet, Parsed, Rows,
+};
 
-pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Vec<Stock> {
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+pub async fn parser(files: Vec<Vec<u8>>, received: DateTime<Utc>) -> Parsed {
+    let (tx, rx) = rows_channel();
     tokio::spawn(async move {
-        for file in files {
-            let cursor = Cursor::new(file);
-            match open_workbook_auto_from_rs(cursor) {
-                Ok(mut wb) => {
-                    if let Some(Ok(table)) = wb.worksheet_range_at(0) {
-                        let tx = tx.clone();
-                        tokio::spawn(parse(table, received, tx));
+        for (index, file) in files.into_iter().enumerate() {
+            match read_sheets(file, true).await {
+                Ok(sheets) => {
+                    for sheet in sheets {
+                        spawn_sheet(&tx, index, sheet, move |table, rows| {
+                            parse(table, received, rows)
+                        })
+                        .await;
                     }
                 }
                 Err(e) => {
                     error!("Ошибка при открытии книги из вложений от 'Братец Лис': {e:?}");
+                    file_error(&tx, &e).await;
                     continue;
                 }
             }
         }
     });
-    let mut result = Vec::new();
-    while let Some(pr) = rx.recv().await {
-        result.push(pr)
-    }
-    result
+    collect(rx).await
 }
 
-async fn parse(table: Range<Data>, received: DateTime<Utc>, tx: UnboundedSender<Stock>) {
-    let mut name = String::new();
-    let re = regex::Regex::new(r#"^[А-я]+\s.+$"#).unwrap();
-    for row in table.rows() {
-        let temp_name = row.get(2).and_then(|d| d.get_string()).unwrap_or_default();
-        if re.is_match(temp_name) {
-            name = temp_name.to_string();
-        } else if let Some(stock) = row
-            .get(6)
-            .and_then(|d| d.to_string().trim().parse::<f64>().ok())
-        {
-            let item = Stock {
-                supplier: "fox".to_string(),
-                name: clear_string(&name),
-                stock,
-                updated: received,
-                id: uuid::Uuid::new_v4(),
-            };
-            if tx.send(item).is_err() {
-                error!("Ошибка отправки строки в канал...")
+fn parse(table: Range<Data>, received: DateTime<Utc>, rows: Rows) {
+    let Some(header) =
+        rows.find_header(&table, &[NAME, ARTICLE, STOCK, RESERVE, INCOMING, ARRIVAL])
+    else {
+        return;
+    };
+    // Заголовок группы это название ковра, в строках товаров только размеры
+    let kinds = classify(&table, &header, &rows, &NAME, &STOCK);
+    let mut groups = Groups::default();
+    for (i, row) in table.rows().enumerate().skip(header.row + 1) {
+        let sent = match kinds[i] {
+            Kind::Group(level) => {
+                groups.enter(level, header.text(row, &NAME));
+                rows.header(i)
             }
+            Kind::Item => match groups.last() {
+                Some(name) => {
+                    let stock = header.get(row, &STOCK).and_then(number).unwrap_or_default();
+                    rows.accept(Stock {
+                        supplier: "fox".to_string(),
+                        name: clear_string(name),
+                        updated: received,
+                        id: uuid::Uuid::new_v4(),
+                        article: header.value(row, &ARTICLE),
+                        ..quantities(&header, row, &STOCK, stock)
+                    })
+                }
+                None => rows.skip(i, row, "Строка до первой группы товаров"),
+            },
+            Kind::Other => rows.skip(i, row, not_a_number(row, &header, &STOCK)),
+        };
+        if !sent {
+            return;
         }
     }
 }
@@ -0,0 +1,132 @@
+use calamine::{Data, DataType, Range};
+
+use super::{
+    columns::{Column, Header},
+    number, Rows,
+};
+
+/// Чем оказалась строка листа, где товары сгруппированы под заголовками
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Заголовок группы с уровнем вложенности, начиная с нуля
+    Group(usize),
+    /// Товар с числом остатка
+    Item,
+    /// Пустая строка, шапка или строка без остатка
+    Other,
+}
+
+/// Размечает строки листа после шапки. Заголовки групп ищутся по оформлению,
+/// которое выгрузки делают сами, поэтому новые группы и бренды поставщика не
+/// требуют правки парсера:
+/// - если строки сгруппированы в Excel, заголовок это строка, за которой идет
+///   более глубокий уровень группировки, а уровень группы берется из нее же;
+/// - иначе, если часть строк набрана жирным, заголовки это жирные строки;
+/// - иначе заголовки это строки с наименованием, но без числа остатка.
+///
+/// Без группировки Excel вложенность выводится из заголовков подряд: несколько
+/// заголовков перед товарами заменяют столько же последних уровней.
+pub fn classify(
+    table: &Range<Data>,
+    header: &Header,
+    rows: &Rows,
+    name: &Column,
+    stock: &Column,
+) -> Vec<Kind> {
+    let mut kinds = vec![Kind::Other; table.height()];
+    let lines = table
+        .rows()
+        .enumerate()
+        .skip(header.row + 1)
+        .filter(|(_, row)| row.iter().any(|d| !d.is_empty()))
+        .collect::<Vec<_>>();
+    let has_name = |row: &[Data]| !header.text(row, name).trim().is_empty();
+    let has_stock = |row: &[Data]| header.get(row, stock).and_then(number).is_some();
+    let item = |row: &[Data]| {
+        if has_stock(row) {
+            Kind::Item
+        } else {
+            Kind::Other
+        }
+    };
+
+    if lines.iter().any(|(i, _)| rows.style(*i).outline > 0) {
+        let outline = |k: usize| lines.get(k).map(|(i, _)| rows.style(*i).outline);
+        let groups = (0..lines.len())
+            .filter(|&k| has_name(lines[k].1) && outline(k + 1) > outline(k))
+            .collect::<Vec<_>>();
+        let mut levels = groups
+            .iter()
+            .filter_map(|&k| outline(k))
+            .collect::<Vec<_>>();
+        levels.sort_unstable();
+        levels.dedup();
+        for (k, (i, row)) in lines.iter().enumerate() {
+            kinds[*i] = match outline(k) {
+                Some(o) if groups.contains(&k) => {
+                    Kind::Group(levels.iter().position(|l| *l == o).unwrap_or_default())
+                }
+                _ => item(row),
+            };
+        }
+        return kinds;
+    }
+
+    let named = lines.iter().filter(|(_, row)| has_name(row));
+    let bold = named.clone().filter(|(i, _)| rows.style(*i).bold).count();
+    let by_bold = bold > 0 && bold < named.count();
+    let is_group = |i: usize, row: &[Data]| {
+        has_name(row)
+            && if by_bold {
+                rows.style(i).bold
+            } else {
+                !has_stock(row)
+            }
+    };
+    let mut depth = 0;
+    let mut run = Vec::new();
+    for (i, row) in &lines {
+        if is_group(*i, row) {
+            run.push(*i);
+            continue;
+        }
+        depth = nest(&mut kinds, &run, depth);
+        run.clear();
+        kinds[*i] = item(row);
+    }
+    nest(&mut kinds, &run, depth);
+    kinds
+}
+
+/// Размечает заголовки, идущие подряд, последними уровнями при текущей
+/// глубине `depth` и возвращает новую глубину
+fn nest(kinds: &mut [Kind], run: &[usize], depth: usize) -> usize {
+    if run.is_empty() {
+        return depth;
+    }
+    let start = depth.saturating_sub(run.len());
+    for (level, i) in run.iter().enumerate() {
+        kinds[*i] = Kind::Group(start + level);
+    }
+    start + run.len()
+}
+
+/// Заголовки групп, в которые вложена текущая строка
+#[derive(Debug, Default)]
+pub struct Groups(Vec<String>);
+
+impl Groups {
+    /// Входит в группу уровня `level`, закрывая группы того же уровня и глубже
+    pub fn enter(&mut self, level: usize, name: String) {
+        self.0.truncate(level);
+        self.0.push(name);
+    }
+    /// Заголовки всех уровней через пробел
+    pub fn path(&self) -> String {
+        self.0.join(" ")
+    }
+    /// Заголовок самой вложенной группы
+    pub fn last(&self) -> Option<&str> {
+        self.0.last().map(String::as_str)
+    }
+}
@@ -1,102 +1,401 @@
-use super::FetchMap;
-use crate::models::Stock;
-use tracing::error;
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use super::{Batch, FetchMap, Outcome, SourceFile};
+use crate::{
+    models::{ParseReport, SheetRow, SkippedRow, Stock},
+    AppError, Result,
+};
+use calamine::{Data, DataType, Range};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tokio::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        oneshot, Semaphore,
+    },
+    time::timeout,
+};
+use tracing::{error, warn};
+
+use columns::{Column, Header, ARRIVAL, INCOMING, RESERVE};
+use format::Sheet;
+use styles::RowStyle;
 
 mod carpetland;
+mod columns;
 mod fancy;
 mod fenix;
+mod format;
 mod fox;
+mod hierarchy;
 mod opus;
 mod ortgraph;
 mod sf;
+mod styles;
+#[cfg(test)]
+mod tests;
 mod vvk;
 mod zefir;
 
-pub async fn parse(fetches: FetchMap) -> Vec<Stock> {
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-    tokio::spawn(async move {
-        for (supplier, (files, received)) in fetches {
-            match supplier.as_str() {
-                "opus" => {
-                    let tx = tx.clone();
-                    let res = opus::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "fox" => {
-                    let tx = tx.clone();
-                    let res = fox::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "fancy" => {
-                    let tx = tx.clone();
-                    let res = fancy::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "carpetland" => {
-                    let tx = tx.clone();
-                    let res = carpetland::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "zefir" => {
-                    let tx = tx.clone();
-                    let res = zefir::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "fenix" => {
-                    let tx = tx.clone();
-                    let res = fenix::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "vvk" => {
-                    let tx = tx.clone();
-                    let res = vvk::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "ortgraph" => {
-                    let tx = tx.clone();
-                    let res = ortgraph::parser(files.clone(), received).await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                "sportflooring" => {
-                    let tx = tx.clone();
-                    let res = sf::parser(
-                        files
-                            .clone()
-                            .first()
-                            .map(|s| s.to_owned())
-                            .unwrap_or_default(),
-                        received,
-                    )
-                    .await;
-                    if tx.send(res).is_err() {
-                        error!("Ошибка при отправке результата парсинга в канал...");
-                    }
-                }
-                _ => continue,
+/// Строк в очереди от задач разбора листов к сборщику остатков поставщика
+const ROWS_BUFFER: usize = 1024;
+/// Сколько листов разбирается одновременно во всех книгах
+const MAX_PARSE_TASKS: usize = 4;
+/// Сколько ждать разбора файлов одного поставщика
+const PARSE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+static PARSE_TASKS: LazyLock<Arc<Semaphore>> =
+    LazyLock::new(|| Arc::new(Semaphore::new(MAX_PARSE_TASKS)));
+
+/// Разбирает файлы поставщиков по очереди. Остатки каждого поставщика отправляются
+/// одним пакетом, чтобы сохраниться в одной транзакции, и результат сохранения
+/// попадает в итог загрузки поставщика.
+pub async fn parse(fetches: FetchMap, tx: &Sender<Batch>) -> Vec<Outcome> {
+    let mut outcomes = Vec::new();
+    for (supplier, (files, received)) in fetches {
+        let Parsed { items, report } = match parse_files(&supplier, files, received).await {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                error!("{e}");
+                outcomes.push(Outcome::failed(supplier, e.to_string()));
+                continue;
+            }
+        };
+        if items.is_empty() {
+            outcomes.push(Outcome::failed(supplier, "Нет строк остатков").with_report(report));
+            continue;
+        }
+        let quantity = items.len();
+        let (saved, result) = oneshot::channel();
+        if tx.send(Batch { items, saved }).await.is_err() {
+            error!("Ошибка при отправке остатков {supplier} на сохранение");
+            break;
+        }
+        let outcome = match result.await {
+            Ok(Ok(_)) => Outcome {
+                supplier,
+                items: quantity,
+                error: None,
+                report: None,
+            },
+            Ok(Err(e)) => Outcome::failed(supplier, format!("{e:?}")),
+            Err(_) => Outcome::failed(supplier, "Сохранение остановлено"),
+        };
+        let outcome = outcome.with_report(report);
+        outcomes.push(outcome);
+    }
+    outcomes
+}
+
+/// Разбирает файлы одного поставщика парсером этого поставщика
+pub async fn parse_files(
+    supplier: &str,
+    files: Vec<SourceFile>,
+    received: DateTime<Utc>,
+) -> Result<Parsed> {
+    let files = files.into_iter().map(|f| f.content).collect::<Vec<_>>();
+    let parsing = async {
+        let parsed = match supplier {
+            "opus" => opus::parser(files, received).await,
+            "fox" => fox::parser(files, received).await,
+            "fancy" => fancy::parser(files, received).await,
+            "carpetland" => carpetland::parser(files, received).await,
+            "zefir" => zefir::parser(files, received).await,
+            "fenix" => fenix::parser(files, received).await,
+            "vvk" => vvk::parser(files, received).await,
+            "ortgraph" => ortgraph::parser(files, received).await,
+            "sportflooring" => {
+                sf::parser(files.into_iter().next().unwrap_or_default(), received).await
+            }
+            _ => {
+                return Err(AppError::Custom(format!(
+                    "Нет парсера для поставщика {supplier}"
+                )))
+            }
+        };
+        Ok(parsed)
+    };
+    match timeout(PARSE_TIMEOUT, parsing).await {
+        Ok(Ok(parsed)) => {
+            if !parsed.report.errors.is_empty() {
+                crate::metrics::get().parse_failure(supplier);
+            }
+            Ok(parsed)
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => {
+            crate::metrics::get().parse_failure(supplier);
+            Err(AppError::Custom(format!(
+                "Превышено время разбора остатков {supplier}"
+            )))
+        }
+    }
+}
+
+/// Остатки поставщика и отчет о том, как разобраны его файлы
+#[derive(Debug, Default, Serialize)]
+pub struct Parsed {
+    pub items: Vec<Stock>,
+    pub report: ParseReport,
+}
+
+/// Что задачи разбора листов сообщают сборщику остатков поставщика
+enum Row {
+    /// Начат разбор листа с таким числом строк
+    Seen(usize),
+    Accepted(Box<Stock>),
+    Header(SheetRow),
+    Skipped(SkippedRow),
+    /// На листе файла с таким номером нашлась шапка
+    Table(usize),
+    /// На листе файла с таким номером нет обязательных колонок
+    NoHeader(usize, String),
+    /// Файл не получилось прочитать
+    Error(String),
+}
+
+/// Строки одного листа для сборщика: остатки и отметки для отчета разбора.
+/// Методы возвращают `false`, когда сборщик уже не ждет строк и разбор листа
+/// пора прекратить.
+struct Rows {
+    tx: Sender<Row>,
+    /// Номер файла среди файлов поставщика
+    file: usize,
+    sheet: String,
+    /// Номер первой строки листа в Excel: пустые строки сверху в таблицу не входят
+    first_row: usize,
+    /// Были ли на листе строки остатков, после них шапки уже не бывает
+    accepted: Cell<bool>,
+    /// Оформление строк листа, см. [`Rows::style`]
+    styles: HashMap<u32, RowStyle>,
+}
+
+impl Rows {
+    fn send(&self, row: Row) -> bool {
+        if self.tx.blocking_send(row).is_err() {
+            error!("Ошибка отправки строки листа {} в канал...", self.sheet);
+            return false;
+        }
+        true
+    }
+    /// Оформление строки таблицы, если формат файла его хранит
+    fn style(&self, index: usize) -> RowStyle {
+        let row = (self.first_row - 1 + index) as u32;
+        self.styles.get(&row).copied().unwrap_or_default()
+    }
+    fn accept(&self, mut item: Stock) -> bool {
+        item.item_id = item.item_key();
+        self.accepted.set(true);
+        self.send(Row::Accepted(Box::new(item)))
+    }
+    /// Отмечает строку шапки или заголовка группы
+    fn header(&self, index: usize) -> bool {
+        self.send(Row::Header(SheetRow {
+            sheet: self.sheet.clone(),
+            row: self.first_row + index,
+        }))
+    }
+    /// Ищет шапку листа и отмечает строки до нее. Лист без обязательных колонок
+    /// не разбирается: в книге бывают листы с инструкцией или контактами, поэтому
+    /// ошибкой это становится, только если таких колонок нет ни на одном листе файла.
+    fn find_header(&self, table: &Range<Data>, columns: &[Column]) -> Option<Header> {
+        let header = match Header::find(table, columns) {
+            Ok(header) => header,
+            Err(e) => {
+                self.send(Row::NoHeader(
+                    self.file,
+                    format!("Лист {}: {e}", self.sheet),
+                ));
+                return None;
+            }
+        };
+        if !self.send(Row::Table(self.file)) {
+            return None;
+        }
+        for (i, row) in table.rows().enumerate().take(header.row) {
+            if !self.skip(i, row, "Строка до шапки таблицы") {
+                return None;
             }
         }
+        self.header(header.row).then_some(header)
+    }
+    /// Отмечает пропущенную строку. Пустые строки не отмечаются, а строки из
+    /// одних подписей без чисел до первой строки остатков считаются шапкой.
+    fn skip(&self, index: usize, row: &[Data], reason: impl Into<String>) -> bool {
+        let cells = row.iter().filter(|d| !d.is_empty()).collect::<Vec<_>>();
+        if cells.is_empty() {
+            return true;
+        }
+        let labels_only = cells.iter().all(|d| {
+            d.get_string()
+                .is_some_and(|s| s.trim().replace(',', ".").parse::<f64>().is_err())
+        });
+        if labels_only && !self.accepted.get() {
+            return self.header(index);
+        }
+        self.send(Row::Skipped(SkippedRow {
+            sheet: self.sheet.clone(),
+            row: self.first_row + index,
+            reason: reason.into(),
+        }))
+    }
+}
+
+/// Причина пропуска строки, в которой нет числа остатка в колонке `column`
+fn not_a_number(row: &[Data], header: &Header, column: &Column) -> String {
+    match header.get(row, column).map(|d| d.to_string()) {
+        Some(value) if !value.trim().is_empty() => format!("Остаток не число: «{value}»"),
+        _ => "Нет остатка".to_string(),
+    }
+}
+
+/// Канал для строк, которые задачи разбора листов отдают парсеру поставщика
+fn rows_channel() -> (Sender<Row>, Receiver<Row>) {
+    channel(ROWS_BUFFER)
+}
+
+/// Собирает остатки и отчет, пока задачи разбора листов не закончатся. Листы без
+/// обязательных колонок попадают в ошибки, только если в их файле нет ни одной
+/// таблицы остатков, иначе в пропущенные листы. Строки одного товара складываются.
+async fn collect(mut rx: Receiver<Row>) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut tables = HashSet::new();
+    let mut no_header = Vec::new();
+    while let Some(row) = rx.recv().await {
+        match row {
+            Row::Seen(rows) => parsed.report.rows_seen += rows,
+            Row::Accepted(item) => {
+                parsed.report.rows_accepted += 1;
+                parsed.items.push(*item);
+            }
+            Row::Header(row) => parsed.report.header(row),
+            Row::Skipped(row) => parsed.report.skip(row),
+            Row::Table(file) => {
+                tables.insert(file);
+            }
+            Row::NoHeader(file, message) => no_header.push((file, message)),
+            Row::Error(e) => parsed.report.errors.push(e),
+        }
+    }
+    parsed.items = merge_items(parsed.items);
+    no_header.sort();
+    for (file, message) in no_header {
+        if tables.contains(&file) {
+            warn!("{message}, лист пропущен");
+            parsed.report.skipped_sheets.push(message);
+        } else {
+            error!("{message}");
+            parsed.report.errors.push(message);
+        }
+    }
+    parsed
+}
+
+/// Складывает строки одного товара каталога в одну, см. [`Stock::merge`]:
+/// у товара один идентификатор и один остаток
+fn merge_items(items: Vec<Stock>) -> Vec<Stock> {
+    let mut merged: Vec<Stock> = Vec::with_capacity(items.len());
+    let mut positions: HashMap<uuid::Uuid, usize> = HashMap::new();
+    for item in items {
+        match positions.get(&item.item_id) {
+            Some(&i) => merged[i].merge(&item),
+            None => {
+                positions.insert(item.item_id, merged.len());
+                merged.push(item);
+            }
+        }
+    }
+    merged
+}
+
+/// Отмечает в отчете файл, который не получилось прочитать
+async fn file_error(tx: &Sender<Row>, e: &AppError) {
+    let _ = tx.send(Row::Error(e.to_string())).await;
+}
+
+/// Читает таблицы файла (из книг все листы или только первый) с именами листов
+/// в пуле блокирующих задач. Формат определяется по содержимому, см. [`format::tables`].
+async fn read_sheets(file: Vec<u8>, first_only: bool) -> Result<Vec<Sheet>> {
+    let task = tokio::task::spawn_blocking(move || format::tables(file, first_only));
+    task.await.map_err(|e| AppError::Custom(format!("{e:?}")))?
+}
+
+/// Запускает разбор листа файла с номером `file` в пуле блокирующих задач, когда
+/// освобождается место среди одновременных задач. Пустые листы пропускаются.
+async fn spawn_sheet(
+    tx: &Sender<Row>,
+    file: usize,
+    sheet: Sheet,
+    parse: impl FnOnce(Range<Data>, Rows) + Send + 'static,
+) {
+    let Sheet {
+        name,
+        table,
+        styles,
+    } = sheet;
+    if table.used_cells().all(|(_, _, d)| d.is_empty()) {
+        return;
+    }
+    let permit = PARSE_TASKS
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("Семафор задач разбора закрыт");
+    let rows = Rows {
+        tx: tx.clone(),
+        file,
+        sheet: name,
+        first_row: table.start().map(|(row, _)| row as usize + 1).unwrap_or(1),
+        accepted: Cell::new(false),
+        styles,
+    };
+    tokio::task::spawn_blocking(move || {
+        if rows.send(Row::Seen(table.height())) {
+            parse(table, rows);
+        }
+        drop(permit);
     });
-    let mut result = Vec::new();
-    while let Some(m) = rx.recv().await {
-        result.extend(m)
+}
+
+/// Число в ячейке или текст, который читается как число: `12,5` это 12.5
+fn number(data: &Data) -> Option<f64> {
+    data.get_float()
+        .or_else(|| data.to_string().trim().replace(',', ".").parse().ok())
+}
+
+/// Дата в ячейке: дата Excel или текст вида `15.02.2025` и `2025-02-15`
+fn date(data: &Data) -> Option<NaiveDate> {
+    data.as_date().or_else(|| {
+        let text = data.to_string();
+        ["%d.%m.%Y", "%Y-%m-%d", "%d.%m.%y"]
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(text.trim(), format).ok())
+    })
+}
+
+/// Количества строки по значению `value` колонки остатка `column`: свободный
+/// остаток, остаток на складе, резерв и ожидаемый приход с датой. Если колонка
+/// остатка не свободный остаток, резерв из нее вычитается. Остальные поля пустые.
+fn quantities(header: &Header, row: &[Data], column: &Column, value: f64) -> Stock {
+    let reserved = header.get(row, &RESERVE).and_then(number);
+    let (stock, on_hand) = if header.is_free(column) {
+        (value, reserved.map(|r| value + r))
+    } else {
+        (value - reserved.unwrap_or_default(), Some(value))
+    };
+    Stock {
+        stock,
+        on_hand,
+        reserved,
+        incoming: header.get(row, &INCOMING).and_then(number),
+        arrival: header.get(row, &ARRIVAL).and_then(date),
+        ..Default::default()
     }
-    result
 }
 
 pub fn clear_string(input: impl AsRef<str>) -> String {
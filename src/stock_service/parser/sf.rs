@@ -1,66 +1,74 @@
-use std::io::Cursor;
-
-use calamine::{open_workbook_auto_from_rs, Data, Range, Reader};
+use calamine::{Data, Range};
 use chrono::{DateTime, Utc};
-use tokio::sync::mpsc::UnboundedSender;
 use tracing::error;
 
 use crate::models::Stock;
 
-use super::clear_string;
+use super::{
+    clear_string, collect,
+    columns::{ARRIVAL, ARTICLE, BRAND, INCOMING, NAME, RESERVE, STOCK},
+    file_error, not_a_number, quantities, read_sheets, rows_channel, spawn_sheet, Parsed, Rows,
+};
 
-pub async fn parser(file: Vec<u8>, received: DateTime<Utc>) -> Vec<Stock> {
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+pub async fn parser(file: Vec<u8>, received: DateTime<Utc>) -> Parsed {
+    let (tx, rx) = rows_channel();
     tokio::spawn(async move {
-        let cursor = Cursor::new(file);
-        match open_workbook_auto_from_rs(cursor) {
-            Ok(mut wb) => {
-                let sheets = wb.worksheets();
-                for (_, table) in sheets {
-                    let tx = tx.clone();
-                    tokio::spawn(parse(table, received, tx));
+        match read_sheets(file, false).await {
+            Ok(sheets) => {
+                for sheet in sheets {
+                    spawn_sheet(&tx, 0, sheet, move |table, rows| {
+                        parse(table, received, rows)
+                    })
+                    .await;
                 }
             }
             Err(e) => {
                 error!("Ошибка при чтении книги из вложений 'Интерьерные решения': {e:?}");
+                file_error(&tx, &e).await;
             }
         }
     });
-    let mut result = Vec::new();
-    while let Some(pr) = rx.recv().await {
-        result.push(pr)
-    }
-    result
+    collect(rx).await
 }
 
-async fn parse(table: Range<Data>, received: DateTime<Utc>, tx: UnboundedSender<Stock>) {
-    for row in table.rows() {
-        if let Some(stock) = row.get(8).and_then(|d| {
+fn parse(table: Range<Data>, received: DateTime<Utc>, rows: Rows) {
+    let Some(header) = rows.find_header(
+        &table,
+        &[NAME, ARTICLE, BRAND, STOCK, RESERVE, INCOMING, ARRIVAL],
+    ) else {
+        return;
+    };
+    for (i, row) in table.rows().enumerate().skip(header.row + 1) {
+        let stock = header.get(row, &STOCK).and_then(|d| {
             d.to_string()
                 .replace(" шт.", "")
                 .replace(" уп.", "")
                 .trim()
                 .parse::<f64>()
                 .ok()
-        }) {
-            if let Some(name) = row.get(3).map(|w| {
-                w.to_string()
-                    .split_whitespace()
-                    .map(|d| d.trim())
-                    .collect::<Vec<_>>()
-                    .join(" ")
-            }) {
-                let item = Stock {
-                    name: clear_string(&name),
-                    stock,
-                    supplier: "sportflooring".to_string(),
-                    updated: received,
-                    id: uuid::Uuid::new_v4(),
-                };
-                if tx.send(item).is_err() {
-                    error!("Ошибка при отправке строки из файла в канал...")
-                }
-            }
+        });
+        let name = header.get(row, &NAME).map(|w| {
+            w.to_string()
+                .split_whitespace()
+                .map(|d| d.trim())
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        let sent = match (stock, name) {
+            (Some(stock), Some(name)) => rows.accept(Stock {
+                name: clear_string(&name),
+                supplier: "sportflooring".to_string(),
+                updated: received,
+                id: uuid::Uuid::new_v4(),
+                brand: header.value(row, &BRAND),
+                article: header.value(row, &ARTICLE),
+                ..quantities(&header, row, &STOCK, stock)
+            }),
+            (Some(_), None) => rows.skip(i, row, "Нет наименования"),
+            (None, _) => rows.skip(i, row, not_a_number(row, &header, &STOCK)),
+        };
+        if !sent {
+            return;
         }
     }
 }
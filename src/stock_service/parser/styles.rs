@@ -0,0 +1,223 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+};
+
+use quick_xml::events::{BytesStart, Event};
+
+/// Оформление строки листа, которое calamine не читает
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RowStyle {
+    /// Уровень группировки строк Excel, 0 без группировки
+    pub outline: u8,
+    /// Первая непустая ячейка строки набрана жирным
+    pub bold: bool,
+}
+
+/// Оформление строк листов xlsx: имя листа, номер строки с нуля и оформление.
+/// Хранятся только строки с группировкой или жирным шрифтом. Для других форматов
+/// и поврежденных книг оформление пустое: оно только подсказывает разбор.
+pub fn xlsx_row_styles(content: &[u8]) -> HashMap<String, HashMap<u32, RowStyle>> {
+    let Ok(mut archive) = zip::ZipArchive::new(Cursor::new(content)) else {
+        return HashMap::new();
+    };
+    let bold = read(&mut archive, "xl/styles.xml")
+        .map(|xml| bold_styles(&xml))
+        .unwrap_or_default();
+    let targets = read(&mut archive, "xl/_rels/workbook.xml.rels")
+        .map(|xml| relationships(&xml))
+        .unwrap_or_default();
+    let sheets = read(&mut archive, "xl/workbook.xml")
+        .map(|xml| sheets(&xml))
+        .unwrap_or_default();
+    let mut result = HashMap::new();
+    for (name, id) in sheets {
+        let Some(target) = targets.get(&id) else {
+            continue;
+        };
+        let path = match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("xl/{target}"),
+        };
+        if let Some(xml) = read(&mut archive, &path) {
+            result.insert(name, rows(&xml, &bold));
+        }
+    }
+    result
+}
+
+fn read(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, path: &str) -> Option<Vec<u8>> {
+    let mut file = archive.by_name(path).ok()?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).ok()?;
+    Some(buffer)
+}
+
+/// Атрибуты элемента по локальным именам, `r:id` становится `id`
+fn attributes(element: &BytesStart) -> HashMap<String, String> {
+    element
+        .attributes()
+        .flatten()
+        .map(|a| {
+            (
+                String::from_utf8_lossy(a.key.local_name().as_ref()).to_string(),
+                String::from_utf8_lossy(&a.value).to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Начало или конец элемента XML
+enum Tag<'a> {
+    /// Начало элемента и признак пустого элемента без конца
+    Open(&'a BytesStart<'a>, bool),
+    /// Конец непустого элемента, локальное имя
+    Close(&'a [u8]),
+}
+
+/// Обходит элементы документа, на ошибке разметки обход заканчивается
+fn walk(xml: &[u8], mut visit: impl FnMut(Tag)) {
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut buffer = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buffer) {
+            Ok(Event::Start(e)) => visit(Tag::Open(&e, false)),
+            Ok(Event::Empty(e)) => visit(Tag::Open(&e, true)),
+            Ok(Event::End(e)) => visit(Tag::Close(e.local_name().as_ref())),
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+}
+
+/// Жирный ли шрифт у каждого стиля ячеек из `cellXfs`
+fn bold_styles(xml: &[u8]) -> Vec<bool> {
+    let mut fonts = Vec::new();
+    let mut styles = Vec::new();
+    let mut section = Vec::<u8>::new();
+    walk(xml, |tag| {
+        let (e, empty) = match tag {
+            Tag::Open(e, empty) => (e, empty),
+            Tag::Close(name) => {
+                if name == section.as_slice() {
+                    section.clear();
+                }
+                return;
+            }
+        };
+        match e.local_name().as_ref() {
+            name @ (b"fonts" | b"cellXfs" | b"cellStyleXfs") if !empty => section = name.to_vec(),
+            b"font" if section == b"fonts" => fonts.push(false),
+            b"b" if section == b"fonts" => {
+                let on = attributes(e)
+                    .get("val")
+                    .is_none_or(|v| v != "0" && v != "false");
+                if let Some(font) = fonts.last_mut() {
+                    *font = on;
+                }
+            }
+            b"xf" if section == b"cellXfs" => {
+                let font = attributes(e)
+                    .get("fontId")
+                    .and_then(|id| id.parse::<usize>().ok())
+                    .unwrap_or_default();
+                styles.push(font);
+            }
+            _ => {}
+        }
+    });
+    styles
+        .into_iter()
+        .map(|font| fonts.get(font).copied().unwrap_or_default())
+        .collect()
+}
+
+/// Файлы листов по идентификаторам связей книги
+fn relationships(xml: &[u8]) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    walk(xml, |tag| {
+        if let Tag::Open(e, _) = tag {
+            if e.local_name().as_ref() == b"Relationship" {
+                let mut a = attributes(e);
+                if let (Some(id), Some(target)) = (a.remove("Id"), a.remove("Target")) {
+                    result.insert(id, target);
+                }
+            }
+        }
+    });
+    result
+}
+
+/// Имена листов и идентификаторы их связей в порядке книги
+fn sheets(xml: &[u8]) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    walk(xml, |tag| {
+        if let Tag::Open(e, _) = tag {
+            if e.local_name().as_ref() == b"sheet" {
+                let mut a = attributes(e);
+                if let (Some(name), Some(id)) = (a.remove("name"), a.remove("id")) {
+                    result.push((name, id));
+                }
+            }
+        }
+    });
+    result
+}
+
+/// Оформление строк листа: уровень группировки из атрибута строки и жирность
+/// первой ячейки со значением
+fn rows(xml: &[u8], bold: &[bool]) -> HashMap<u32, RowStyle> {
+    let mut result = HashMap::new();
+    let mut row = 0u32;
+    // Номер строки без атрибута `r`: следующий за предыдущей строкой
+    let mut next = 0u32;
+    let mut current = RowStyle::default();
+    let mut bold_decided = false;
+    let mut cell_style = 0usize;
+    walk(xml, |tag| {
+        let (e, empty) = match tag {
+            Tag::Open(e, empty) => (e, empty),
+            Tag::Close(name) => {
+                if name == b"row" && current != RowStyle::default() {
+                    result.insert(row, current);
+                }
+                return;
+            }
+        };
+        match e.local_name().as_ref() {
+            b"row" => {
+                let a = attributes(e);
+                row = a
+                    .get("r")
+                    .and_then(|r| r.parse::<u32>().ok())
+                    .map(|r| r.saturating_sub(1))
+                    .unwrap_or(next);
+                next = row + 1;
+                current = RowStyle {
+                    outline: a
+                        .get("outlineLevel")
+                        .and_then(|l| l.parse().ok())
+                        .unwrap_or_default(),
+                    bold: false,
+                };
+                bold_decided = false;
+                if empty && current.outline > 0 {
+                    result.insert(row, current);
+                }
+            }
+            b"c" => {
+                cell_style = attributes(e)
+                    .get("s")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_default();
+            }
+            b"v" | b"is" if !bold_decided => {
+                current.bold = bold.get(cell_style).copied().unwrap_or_default();
+                bold_decided = true;
+            }
+            _ => {}
+        }
+    });
+    result
+}
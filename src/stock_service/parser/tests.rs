@@ -0,0 +1,103 @@
+//! Проверка парсеров на образцах файлов поставщиков.
+//!
+//! Образцы лежат в `tests/fixtures/parsers/<поставщик>/`, ожидаемые остатки и
+//! отчет разбора рядом в `tests/fixtures/parsers/<поставщик>.json`. Если формат
+//! поставщика изменился намеренно, снимки пересоздаются командой `just update-snapshots`
+//! (`UPDATE_SNAPSHOTS=1 cargo test parser::tests`), а изменения в JSON
+//! просматриваются в диффе.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{TimeZone, Utc};
+
+use super::{parse_files, SourceFile};
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/parsers")
+}
+
+/// Файлы образца поставщика в порядке имен
+fn read_fixture(supplier: &str) -> Vec<SourceFile> {
+    let dir = fixtures().join(supplier);
+    let mut paths = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Нет образцов {}: {e}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| SourceFile {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            content: std::fs::read(&path).unwrap(),
+        })
+        .collect()
+}
+
+/// Остатки без случайных идентификаторов и отчет разбора в стабильном порядке:
+/// листы разбираются параллельно, и строки приходят в разном порядке
+async fn snapshot(supplier: &str) -> String {
+    let received = Utc.with_ymd_and_hms(2025, 1, 15, 9, 0, 0).unwrap();
+    let mut parsed = parse_files(supplier, read_fixture(supplier), received)
+        .await
+        .unwrap_or_else(|e| panic!("Ошибка разбора образца {supplier}: {e:?}"));
+    let mut items = parsed
+        .items
+        .into_iter()
+        .map(|item| {
+            let mut value = serde_json::to_value(item).unwrap();
+            value.as_object_mut().unwrap().remove("id");
+            value
+        })
+        .collect::<Vec<_>>();
+    items.sort_by_key(|value| value.to_string());
+    parsed
+        .report
+        .headers
+        .sort_by(|a, b| (&a.sheet, a.row).cmp(&(&b.sheet, b.row)));
+    parsed
+        .report
+        .skipped
+        .sort_by(|a, b| (&a.sheet, a.row).cmp(&(&b.sheet, b.row)));
+    let snapshot = serde_json::json!({ "items": items, "report": parsed.report });
+    serde_json::to_string_pretty(&snapshot).unwrap() + "\n"
+}
+
+async fn check(supplier: &str) {
+    let actual = snapshot(supplier).await;
+    let path = fixtures().join(format!("{supplier}.json"));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        expected == actual,
+        "Остатки {supplier} не совпадают со снимком {}.\n\
+         Если формат изменился намеренно, пересоздайте снимки: just update-snapshots\n\
+         Получено:\n{actual}",
+        path.display()
+    );
+}
+
+macro_rules! golden {
+    ($($supplier:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $supplier() {
+                check(stringify!($supplier)).await;
+            }
+        )*
+    };
+}
+
+golden!(
+    carpetland,
+    fancy,
+    fenix,
+    fox,
+    opus,
+    ortgraph,
+    sportflooring,
+    vvk,
+    zefir
+);
@@ -0,0 +1,110 @@
+mod ortgraph;
+mod sf;
+mod vvk;
+
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use chrono::{DateTime, Utc};
+
+use crate::{config::Config, AppError, Result};
+
+use super::{FetchMap, SourceFile};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Файлы остатков, скачанные из источника, и время, на которое они актуальны
+pub struct WebFiles {
+    pub files: Vec<SourceFile>,
+    pub received: DateTime<Utc>,
+}
+
+/// Источник остатков в сети. Источник сам знает свои учетные данные и сам
+/// определяет дату остатков, общий у источников только HTTP клиент.
+pub trait WebSource: Send + Sync {
+    /// Код поставщика, он же имя компонента в `/health`
+    fn supplier(&self) -> &'static str;
+    fn fetch<'a>(&'a self, client: &'a reqwest::Client) -> BoxFuture<'a, Result<WebFiles>>;
+}
+
+/// Источник с расписанием
+#[derive(Clone)]
+pub struct Registered {
+    pub source: Arc<dyn WebSource>,
+    /// Через сколько часов после удачной загрузки скачивать остатки снова
+    pub interval_hours: u64,
+}
+
+#[derive(Clone)]
+pub struct Spider {
+    sources: Vec<Registered>,
+    client: reqwest::Client,
+}
+
+impl Spider {
+    pub fn new(config: &Config) -> Result<Self> {
+        let cookie_store = std::sync::Arc::new(reqwest::cookie::Jar::default());
+        let mut def_head = reqwest::header::HeaderMap::new();
+        let v = reqwest::header::HeaderValue::from_str(
+            "https://www.yandex.ru/clck/jsredir?from=yandex.ru;suggest;browser&text=",
+        )
+        .map_err(|e| AppError::ReqwestError(e.to_string()))?;
+        def_head.insert(reqwest::header::REFERER, v);
+        let client = reqwest::Client::builder()
+            .gzip(true)
+            .default_headers(def_head)
+            .cookie_store(true)
+            .cookie_provider(cookie_store.clone())
+            .user_agent("Mozilla/5.0 (Linux; Android 6.0; Nexus 5 Build/MRA58N) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36")
+            .build()?;
+        let mut sources: Vec<Arc<dyn WebSource>> = Vec::new();
+        if let Some(ortgraph) = &config.ortgraph {
+            sources.push(Arc::new(ortgraph::Ortgraph::new(ortgraph.clone())));
+        }
+        if config.vvk_enabled {
+            sources.push(Arc::new(vvk::Vvk));
+        }
+        if config.sportflooring_enabled {
+            sources.push(Arc::new(sf::Sportflooring));
+        }
+        let sources = sources
+            .into_iter()
+            .map(|source| Registered {
+                interval_hours: config.web_interval(source.supplier()),
+                source,
+            })
+            .collect();
+        Ok(Spider { sources, client })
+    }
+    /// Включен ли хотя бы один источник остатков из сети
+    pub fn is_enabled(&self) -> bool {
+        !self.sources.is_empty()
+    }
+    pub fn sources(&self) -> &[Registered] {
+        &self.sources
+    }
+    /// Скачивает остатки одного источника. Источник без файлов это ошибка:
+    /// значит, изменилась страница или ссылка.
+    pub async fn fetch(&self, source: &dyn WebSource) -> Result<FetchMap> {
+        let supplier = source.supplier();
+        let fetched = source.fetch(&self.client).await?;
+        if fetched.files.iter().all(|f| f.content.is_empty()) {
+            return Err(AppError::Custom(format!(
+                "{supplier}: в источнике не найдено файлов остатков"
+            )));
+        }
+        Ok(HashMap::from([(
+            supplier.to_string(),
+            (fetched.files, fetched.received),
+        )]))
+    }
+    /// Скачивает остатки всех источников один раз. Ошибка источника не мешает
+    /// остальным, результат возвращается по каждому источнику.
+    pub async fn get_web(&self) -> Vec<(&'static str, Result<FetchMap>)> {
+        let mut results = Vec::new();
+        for registered in &self.sources {
+            let source = registered.source.as_ref();
+            results.push((source.supplier(), self.fetch(source).await));
+        }
+        results
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::{config::OrtgraphConfig, AppError, Result};
+
+use super::{BoxFuture, SourceFile, WebFiles, WebSource};
+
+const BASE_URI: &str = "https://ortgraph.ru";
+const STOCK: &str = "remains/";
+const AUTH: &str = "auth/";
+const PERSONAL: &str = "/personal/";
+
+/// Остатки из личного кабинета Ортграф, нужен логин дилера
+pub struct Ortgraph {
+    config: OrtgraphConfig,
+}
+
+impl Ortgraph {
+    pub fn new(config: OrtgraphConfig) -> Self {
+        Self { config }
+    }
+    async fn files(&self, client: &reqwest::Client) -> Result<WebFiles> {
+        let mut auth_uri = BASE_URI.to_string();
+        auth_uri.push('/');
+        auth_uri.push_str(AUTH);
+        let mut form = HashMap::new();
+        form.insert("AUTH_FORM", "Y");
+        form.insert("TYPE", "AUTH");
+        form.insert("backurl", "/auth/");
+        form.insert("USER_LOGIN", &self.config.username);
+        form.insert("USER_PASSWORD", &self.config.password);
+        form.insert("Login", "Войти");
+        let response = client
+            .post(&auth_uri)
+            .query(&[("login", "yes")])
+            .form(&form)
+            .send()
+            .await?;
+        if response.status() != reqwest::StatusCode::OK {
+            let status = response.status();
+            let b = response.text().await?;
+            tracing::debug!("Ответ Ортграф на вход:\n{b:#?}");
+            return Err(AppError::Custom(format!(
+                "Не получилось войти в личный кабинет Ортграф: {status}"
+            )));
+        }
+        let mut stock_uri = BASE_URI.to_string();
+        stock_uri.push_str(PERSONAL);
+        stock_uri.push_str(STOCK);
+        let response = client
+            .get(&stock_uri)
+            .query(&[("login", "yes")])
+            .send()
+            .await?;
+        let body = response.text().await?;
+        let links = get_links(body);
+        let mut files = Vec::new();
+        let mut received = None;
+        for path in links {
+            let mut uri = BASE_URI.to_string();
+            uri.push_str(&path);
+            let temp_res = client.get(&uri).send().await?;
+            // Остатки актуальны на время изменения самого нового файла
+            received = received.max(last_modified(&temp_res));
+            let bytes = temp_res.bytes().await?.as_ref().to_vec();
+            let name = path.rsplit('/').next().unwrap_or_default().to_string();
+            files.push(SourceFile {
+                name,
+                content: bytes,
+            });
+        }
+        Ok(WebFiles {
+            files,
+            received: received.unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+impl WebSource for Ortgraph {
+    fn supplier(&self) -> &'static str {
+        "ortgraph"
+    }
+    fn fetch<'a>(&'a self, client: &'a reqwest::Client) -> BoxFuture<'a, Result<WebFiles>> {
+        Box::pin(self.files(client))
+    }
+}
+
+fn last_modified(response: &reqwest::Response) -> Option<DateTime<Utc>> {
+    let value = response
+        .headers()
+        .get(reqwest::header::LAST_MODIFIED)?
+        .to_str()
+        .ok()?;
+    DateTime::parse_from_rfc2822(value).ok().map(|d| d.to_utc())
+}
+
+fn get_links(body: String) -> Vec<String> {
+    let mut result = Vec::new();
+    let dom = tl::parse(&body, tl::ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+    let links = dom.query_selector("a[href]").unwrap();
+    for link in links {
+        let tag = link.get(parser).unwrap().as_tag().unwrap();
+        let l = tag
+            .attributes()
+            .get("href")
+            .flatten()
+            .unwrap()
+            .as_utf8_str()
+            .to_string();
+        let annotation = tag.inner_html(parser);
+        if l.contains(".xls")
+            && l.contains("upload")
+            && (annotation.to_lowercase().contains("ковр")
+                || annotation.to_lowercase().contains("напол"))
+        {
+            // info!("Got link for {annotation}: {l}");
+            result.push(l)
+        }
+    }
+    result
+}
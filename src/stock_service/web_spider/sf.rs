@@ -0,0 +1,65 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{AppError, Result};
+
+use super::{BoxFuture, SourceFile, WebFiles, WebSource};
+
+const BASE_URI: &str = "https://cloud.mail.ru/public/SA23/oHuEdQLmS";
+
+/// Остатки SportFlooring из публичной папки Облака Mail.ru. Дата остатков
+/// записана в имени файла
+pub struct Sportflooring;
+
+impl Sportflooring {
+    async fn files(&self, client: &reqwest::Client) -> Result<WebFiles> {
+        let text = client.get(BASE_URI).send().await?.text().await?;
+        let weblink_re =
+            regex::Regex::new(r#""weblink_get":\S"count":"1","url":"(?<url>\S+/no)"},"#).unwrap();
+        let filename_re = regex::Regex::new(
+            r#""name":"Остатки\s+СФ\s+на\s+(?<date>[\d.]+)\s+Клиентские\s+Ковровые\s*.+xlsx","weblink":"(?<url>[A-zА-я/\s\d.]+)","#,
+        ).unwrap();
+        let weblink = weblink_re
+            .captures(&text)
+            .and_then(|c| c.name("url"))
+            .ok_or(AppError::Custom("Не найдена ссылка на скачивание".into()))?
+            .as_str();
+        let filename_capture = filename_re
+            .captures(&text)
+            .ok_or(AppError::Custom("Не найден файл остатков".into()))?;
+        let filename = filename_capture.name("url").unwrap().as_str();
+        let date_str = filename_capture.name("date").unwrap().as_str();
+        let received = received(date_str).unwrap_or_else(|| {
+            tracing::warn!("Не получилось прочитать дату остатков SportFlooring: {date_str}");
+            Utc::now()
+        });
+        let uri = format!("{weblink}/{filename}");
+        let file = client.get(&uri).send().await?.bytes().await?.to_vec();
+        let name = filename.rsplit('/').next().unwrap_or(filename).to_string();
+        Ok(WebFiles {
+            files: vec![SourceFile {
+                name,
+                content: file,
+            }],
+            received,
+        })
+    }
+}
+
+impl WebSource for Sportflooring {
+    fn supplier(&self) -> &'static str {
+        "sportflooring"
+    }
+    fn fetch<'a>(&'a self, client: &'a reqwest::Client) -> BoxFuture<'a, Result<WebFiles>> {
+        Box::pin(self.files(client))
+    }
+}
+
+/// Дата из имени файла: `01.02.2025` или `01.02.25`
+fn received(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim_end_matches('.');
+    ["%d.%m.%Y", "%d.%m.%y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
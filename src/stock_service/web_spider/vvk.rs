@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::Result;
+
+use super::{BoxFuture, SourceFile, WebFiles, WebSource};
+
+const BASE_URI: &str = "https://disk.yandex.ru/d/1qA555p_DbQiaQ";
+const RESOURCES_URI: &str = "https://cloud-api.yandex.net:443/v1/disk/public/resources";
+
+/// Остатки ВВК из публичной папки Яндекс Диска
+pub struct Vvk;
+
+impl Vvk {
+    async fn files(&self, client: &reqwest::Client) -> Result<WebFiles> {
+        let mut result = Vec::new();
+        let response = client
+            .get(RESOURCES_URI)
+            .query(&[("public_key", BASE_URI)])
+            .send()
+            .await?;
+        let root: Root = response.json().await?;
+        // Остатки актуальны на время изменения самого нового файла в папке
+        let received = root.embedded.items.iter().filter_map(|i| i.modified).max();
+        for i in root.embedded.items {
+            let file = client.get(i.file).send().await?.bytes().await?.to_vec();
+            result.push(SourceFile {
+                name: i.name,
+                content: file,
+            })
+        }
+        Ok(WebFiles {
+            files: result,
+            received: received.unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+impl WebSource for Vvk {
+    fn supplier(&self) -> &'static str {
+        "vvk"
+    }
+    fn fetch<'a>(&'a self, client: &'a reqwest::Client) -> BoxFuture<'a, Result<WebFiles>> {
+        Box::pin(self.files(client))
+    }
+}
+
+#[derive(Deserialize)]
+struct Root {
+    #[serde(rename = "_embedded")]
+    pub embedded: Embedded,
+}
+#[derive(Deserialize)]
+struct Embedded {
+    items: Vec<Item>,
+}
+#[derive(Deserialize)]
+struct Item {
+    #[serde(default)]
+    name: String,
+    file: String,
+    #[serde(default)]
+    modified: Option<DateTime<Utc>>,
+}
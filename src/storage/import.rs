@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    models::{Import, RawFile},
+    Result,
+};
+
+#[derive(Clone)]
+pub struct ImportStorage {
+    pool: sqlx::PgPool,
+}
+
+impl ImportStorage {
+    pub fn new(pool: sqlx::PgPool) -> ImportStorage {
+        ImportStorage { pool }
+    }
+    pub async fn add(&self, import: &Import) -> Result<()> {
+        let query = "INSERT INTO imports(id, supplier, source, items, error, mail_uids, mail_action, superseded_by, report, created) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)";
+        sqlx::query(query)
+            .bind(import.id)
+            .bind(&import.supplier)
+            .bind(&import.source)
+            .bind(import.items)
+            .bind(&import.error)
+            .bind(&import.mail_uids)
+            .bind(&import.mail_action)
+            .bind(import.superseded_by)
+            .bind(&import.report)
+            .bind(import.created)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+    /// Загрузки остатков, новые первыми
+    pub async fn list(
+        &self,
+        supplier: Option<&str>,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<Import>> {
+        let query = "SELECT id, supplier, source, items, error, mail_uids, mail_action, superseded_by, report, created FROM imports WHERE ($1::VARCHAR IS NULL OR supplier = $1) ORDER BY created DESC LIMIT $2 OFFSET $3";
+        let results = sqlx::query_as::<_, Import>(query)
+            .bind(supplier)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+    /// Сохраняет полученный файл. Одинаковые файлы поставщика хранятся один раз,
+    /// у уже сохраненного файла обновляется дата последнего получения, а сам файл
+    /// записывается в каждое получение, см. [`ImportStorage::batch`]. Возвращает SHA-256.
+    pub async fn archive(
+        &self,
+        supplier: &str,
+        source: &str,
+        filename: &str,
+        content: &[u8],
+        received: DateTime<Utc>,
+    ) -> Result<String> {
+        let sha256 = hex::encode(Sha256::digest(content));
+        let mut tx = self.pool.begin().await?;
+        let query = "INSERT INTO raw_files(sha256, supplier, source, filename, size, received, content) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (sha256, supplier) DO UPDATE SET received = GREATEST(raw_files.received, EXCLUDED.received)";
+        sqlx::query(query)
+            .bind(&sha256)
+            .bind(supplier)
+            .bind(source)
+            .bind(filename)
+            .bind(content.len() as i64)
+            .bind(received)
+            .bind(content)
+            .execute(&mut *tx)
+            .await?;
+        let query = "INSERT INTO raw_file_receipts(sha256, supplier, received, filename) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING";
+        sqlx::query(query)
+            .bind(&sha256)
+            .bind(supplier)
+            .bind(received)
+            .bind(filename)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(sha256)
+    }
+    /// Сохраненные файлы без содержимого, новые первыми
+    pub async fn files(
+        &self,
+        supplier: Option<&str>,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<RawFile>> {
+        let query = "SELECT sha256, supplier, source, filename, size, received, created FROM raw_files WHERE ($1::VARCHAR IS NULL OR supplier = $1) ORDER BY received DESC LIMIT $2 OFFSET $3";
+        let results = sqlx::query_as::<_, RawFile>(query)
+            .bind(supplier)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+    /// Сохраненные файлы с таким содержимым, по одному на поставщика
+    pub async fn file(&self, sha256: &str, supplier: Option<&str>) -> Result<Vec<RawFile>> {
+        let query = "SELECT sha256, supplier, source, filename, size, received, created FROM raw_files WHERE sha256 = $1 AND ($2::VARCHAR IS NULL OR supplier = $2) ORDER BY supplier";
+        let results = sqlx::query_as::<_, RawFile>(query)
+            .bind(sha256)
+            .bind(supplier)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+    /// Файлы поставщика из одного получения, включая файлы, которые не изменились
+    /// с прошлых получений, с именами и датой этого получения
+    pub async fn batch(&self, supplier: &str, received: DateTime<Utc>) -> Result<Vec<RawFile>> {
+        let query = "SELECT f.sha256, f.supplier, f.source, r.filename, f.size, r.received, f.created FROM raw_file_receipts r JOIN raw_files f USING (sha256, supplier) WHERE r.supplier = $1 AND r.received = $2 ORDER BY r.filename";
+        let results = sqlx::query_as::<_, RawFile>(query)
+            .bind(supplier)
+            .bind(received)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+    /// Содержимое сохраненного файла
+    pub async fn content(&self, file: &RawFile) -> Result<Vec<u8>> {
+        let query = "SELECT content FROM raw_files WHERE sha256 = $1 AND supplier = $2";
+        let content = sqlx::query_scalar::<_, Vec<u8>>(query)
+            .bind(&file.sha256)
+            .bind(&file.supplier)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(content)
+    }
+}
@@ -1,2 +1,9 @@
+mod import;
 mod stock;
-pub use stock::StockStorage;
+mod supplier;
+mod system;
+
+pub use import::ImportStorage;
+pub use stock::{StockFilter, StockStorage};
+pub use supplier::SupplierStorage;
+pub use system::SystemStorage;
@@ -1,5 +1,25 @@
-use crate::{models::Stock, Result};
-use std::collections::HashSet;
+use crate::{
+    metrics,
+    models::{Stock, SupplierItem, SupplierSummary},
+    search, Result,
+};
+use std::collections::HashMap;
+
+/// Условия отбора остатков, пустые поля не ограничивают выборку.
+/// Свойства сравниваются без учета регистра.
+#[derive(Clone, Debug, Default)]
+pub struct StockFilter {
+    /// Слова, которые должны встречаться в наименовании или артикуле
+    pub search: Option<String>,
+    pub supplier: Option<String>,
+    /// Товар из каталога поставщика
+    pub item_id: Option<uuid::Uuid>,
+    pub brand: Option<String>,
+    pub collection: Option<String>,
+    pub color: Option<String>,
+    pub width: Option<f64>,
+    pub article: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct StockStorage {
@@ -11,31 +31,124 @@ impl StockStorage {
         StockStorage { pool }
     }
     pub async fn update(&self, input: &[Stock]) -> Result<(u64, u64)> {
-        let mut suppliers = HashSet::new();
+        let mut suppliers = HashMap::new();
         for supplier in input.iter().map(|s| s.supplier.clone()) {
-            suppliers.insert(supplier);
+            *suppliers.entry(supplier).or_insert(0) += 1;
         }
+        let metrics = metrics::get();
         let mut tx = self.pool.begin().await?;
         let mut deleted = 0;
-        for supplier in suppliers {
+        let mut deleted_by_supplier = Vec::new();
+        for supplier in suppliers.keys() {
             let query = "DELETE FROM stock WHERE supplier=$1";
             let qr = sqlx::query(query).bind(supplier).execute(&mut *tx).await?;
             deleted += qr.rows_affected();
+            deleted_by_supplier.push((supplier, qr.rows_affected()));
         }
-        let query_string = "INSERT INTO stock(supplier, name, stock, updated) ";
+        upsert_items(&mut tx, &catalog(input)).await?;
+        let query_string = "INSERT INTO stock(item_id, supplier, name, stock, on_hand, reserved, incoming, arrival, updated, search_name, brand, collection, color, width, article) ";
         let mut query_builder = sqlx::QueryBuilder::new(query_string);
         query_builder.push_values(input, |mut b, stock| {
-            b.push_bind(&stock.supplier)
+            b.push_bind(stock.item_id)
+                .push_bind(&stock.supplier)
                 .push_bind(&stock.name)
                 .push_bind(stock.stock)
-                .push_bind(stock.updated);
+                .push_bind(stock.on_hand)
+                .push_bind(stock.reserved)
+                .push_bind(stock.incoming)
+                .push_bind(stock.arrival)
+                .push_bind(stock.updated)
+                .push_bind(search_name(&stock.name, stock.article.as_deref()))
+                .push_bind(&stock.brand)
+                .push_bind(&stock.collection)
+                .push_bind(&stock.color)
+                .push_bind(stock.width)
+                .push_bind(&stock.article);
         });
         let query = query_builder.build();
         let results = query.execute(&mut *tx).await?;
         let inserted = results.rows_affected();
         tx.commit().await?;
+        let now = chrono::Utc::now().timestamp() as f64;
+        for (supplier, count) in deleted_by_supplier {
+            metrics
+                .rows_deleted
+                .with_label_values(&[supplier])
+                .inc_by(count);
+        }
+        for (supplier, count) in suppliers.iter() {
+            metrics
+                .rows_imported
+                .with_label_values(&[supplier])
+                .inc_by(*count);
+            metrics.last_import.with_label_values(&[supplier]).set(now);
+        }
         Ok((deleted, inserted))
     }
+    /// Привязывает к каталогу остатки, загруженные до него: миграция дала им
+    /// случайные идентификаторы товаров. Идентификатор считается так же, как при
+    /// загрузке, строки с товаром из каталога не меняются, поэтому при следующих
+    /// запусках делать нечего.
+    pub async fn link_items(&self) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let query = "SELECT * FROM stock WHERE NOT EXISTS \
+                     (SELECT 1 FROM supplier_items WHERE supplier_items.id = stock.item_id)";
+        let mut rows: Vec<Stock> = sqlx::query_as(query).fetch_all(&mut *tx).await?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        for row in &mut rows {
+            row.item_id = row.item_key();
+        }
+        upsert_items(&mut tx, &catalog(&rows)).await?;
+        let query = "UPDATE stock SET item_id = linked.item_id \
+                     FROM UNNEST($1::uuid[], $2::uuid[]) AS linked(id, item_id) \
+                     WHERE stock.id = linked.id";
+        let result = sqlx::query(query)
+            .bind(rows.iter().map(|r| r.id).collect::<Vec<_>>())
+            .bind(rows.iter().map(|r| r.item_id).collect::<Vec<_>>())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+    /// Заполняет строку поиска остаткам и товарам каталога, добавленным до нее, так
+    /// же, как при загрузке. Заполненные строки не меняются, поэтому при следующих
+    /// запусках делать нечего.
+    pub async fn fill_search_names(&self) -> Result<u64> {
+        let query = "SELECT * FROM stock WHERE search_name = ''";
+        let rows: Vec<Stock> = sqlx::query_as(query).fetch_all(&self.pool).await?;
+        let stock = rows
+            .iter()
+            .map(|r| (r.id, search_name(&r.name, r.article.as_deref())));
+        let mut filled = self.fill_search_name("stock", stock.collect()).await?;
+        let query = "SELECT * FROM supplier_items WHERE search_name = ''";
+        let rows: Vec<SupplierItem> = sqlx::query_as(query).fetch_all(&self.pool).await?;
+        let items = rows
+            .iter()
+            .map(|r| (r.id, search_name(&r.name, r.article.as_deref())));
+        filled += self
+            .fill_search_name("supplier_items", items.collect())
+            .await?;
+        Ok(filled)
+    }
+    async fn fill_search_name(&self, table: &str, rows: Vec<(uuid::Uuid, String)>) -> Result<u64> {
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let (ids, names): (Vec<_>, Vec<_>) = rows.into_iter().unzip();
+        let query = format!(
+            "UPDATE {table} SET search_name = filled.search_name \
+             FROM UNNEST($1::uuid[], $2::varchar[]) AS filled(id, search_name) \
+             WHERE {table}.id = filled.id"
+        );
+        let result = sqlx::query(&query)
+            .bind(ids)
+            .bind(names)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
     pub async fn get(&self, limit: i32, offset: i32) -> Result<Vec<Stock>> {
         let query = "SELECT * FROM stock LIMIT $1 OFFSET $2";
         let results = sqlx::query_as::<_, Stock>(query)
@@ -45,4 +158,195 @@ impl StockStorage {
             .await?;
         Ok(results)
     }
+    /// Поиск по нормализованному наименованию: все слова запроса должны встречаться в
+    /// наименовании, результаты упорядочены по похожести на запрос
+    pub async fn search(&self, query: &str, limit: i32, offset: i32) -> Result<Vec<Stock>> {
+        let filter = StockFilter {
+            search: Some(query.to_string()),
+            ..Default::default()
+        };
+        self.find(&filter, limit, offset).await
+    }
+    /// Остатки, подходящие под фильтр. Со словами поиска результаты упорядочены
+    /// по похожести на запрос, без них по поставщику и наименованию.
+    pub async fn find(&self, filter: &StockFilter, limit: i32, offset: i32) -> Result<Vec<Stock>> {
+        let words = filter
+            .search
+            .as_deref()
+            .map(search::tokens)
+            .unwrap_or_default();
+        let mut query_builder = sqlx::QueryBuilder::new("SELECT * FROM stock WHERE TRUE");
+        for word in &words {
+            query_builder
+                .push(" AND search_name LIKE ")
+                .push_bind(like_pattern(word));
+        }
+        if let Some(supplier) = &filter.supplier {
+            query_builder
+                .push(" AND supplier = ")
+                .push_bind(supplier.clone());
+        }
+        if let Some(item_id) = filter.item_id {
+            query_builder.push(" AND item_id = ").push_bind(item_id);
+        }
+        for (column, value) in [
+            ("brand", &filter.brand),
+            ("collection", &filter.collection),
+            ("color", &filter.color),
+            ("article", &filter.article),
+        ] {
+            if let Some(value) = value {
+                query_builder
+                    .push(format!(" AND upper({column}) = upper("))
+                    .push_bind(value.trim().to_string())
+                    .push(")");
+            }
+        }
+        if let Some(width) = filter.width {
+            query_builder
+                .push(" AND abs(width - ")
+                .push_bind(width)
+                .push(") < 0.001");
+        }
+        if words.is_empty() {
+            query_builder.push(" ORDER BY supplier, name");
+        } else {
+            query_builder
+                .push(" ORDER BY similarity(search_name, ")
+                .push_bind(words.join(" "))
+                .push(") DESC, stock DESC");
+        }
+        query_builder
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let results = query_builder
+            .build_query_as::<Stock>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+    /// Каталог товаров поставщиков: недавно виденные первыми. Слова поиска
+    /// нормализуются и ищутся в наименовании и артикуле, как в поиске остатков.
+    pub async fn items(
+        &self,
+        supplier: Option<&str>,
+        search: Option<&str>,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<SupplierItem>> {
+        let mut query_builder = sqlx::QueryBuilder::new("SELECT * FROM supplier_items WHERE TRUE");
+        if let Some(supplier) = supplier {
+            query_builder
+                .push(" AND supplier = ")
+                .push_bind(supplier.to_string());
+        }
+        for word in search.map(search::tokens).unwrap_or_default() {
+            query_builder
+                .push(" AND search_name LIKE ")
+                .push_bind(like_pattern(&word));
+        }
+        query_builder
+            .push(" ORDER BY last_seen DESC, name LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let results = query_builder
+            .build_query_as::<SupplierItem>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+    pub async fn item(&self, id: uuid::Uuid) -> Result<Option<SupplierItem>> {
+        let query = "SELECT * FROM supplier_items WHERE id = $1";
+        let result = sqlx::query_as::<_, SupplierItem>(query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(result)
+    }
+    pub async fn suppliers(&self) -> Result<Vec<SupplierSummary>> {
+        let query = "SELECT supplier, COUNT(*) AS items, MAX(updated) AS updated FROM stock GROUP BY supplier ORDER BY updated DESC";
+        let results = sqlx::query_as::<_, SupplierSummary>(query)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+}
+
+/// Строка для поиска: наименование и артикул, по которому тоже ищут
+fn search_name(name: &str, article: Option<&str>) -> String {
+    match article {
+        Some(article) => search::normalize(format!("{name} {article}")),
+        None => search::normalize(name),
+    }
+}
+
+/// Шаблон LIKE для слова поиска: `%`, `_` и `\` из запроса ищутся как есть
+fn like_pattern(word: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in word.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Добавляет товары в каталог или обновляет наименование и даты уже известных.
+/// Свойства, которых нет в новых остатках, остаются прежними.
+async fn upsert_items(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    items: &[SupplierItem],
+) -> Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    let query_string = "INSERT INTO supplier_items(id, supplier, name, brand, collection, color, width, article, first_seen, last_seen, search_name) ";
+    let mut query_builder = sqlx::QueryBuilder::new(query_string);
+    query_builder.push_values(items, |mut b, item| {
+        b.push_bind(item.id)
+            .push_bind(&item.supplier)
+            .push_bind(&item.name)
+            .push_bind(&item.brand)
+            .push_bind(&item.collection)
+            .push_bind(&item.color)
+            .push_bind(item.width)
+            .push_bind(&item.article)
+            .push_bind(item.first_seen)
+            .push_bind(item.last_seen)
+            .push_bind(search_name(&item.name, item.article.as_deref()));
+    });
+    query_builder.push(
+        " ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, \
+         search_name = EXCLUDED.search_name, \
+         brand = COALESCE(EXCLUDED.brand, supplier_items.brand), \
+         collection = COALESCE(EXCLUDED.collection, supplier_items.collection), \
+         color = COALESCE(EXCLUDED.color, supplier_items.color), \
+         width = COALESCE(EXCLUDED.width, supplier_items.width), \
+         article = COALESCE(EXCLUDED.article, supplier_items.article), \
+         first_seen = LEAST(supplier_items.first_seen, EXCLUDED.first_seen), \
+         last_seen = GREATEST(supplier_items.last_seen, EXCLUDED.last_seen)",
+    );
+    query_builder.build().execute(&mut **tx).await?;
+    Ok(())
+}
+
+/// Товары каталога из строк остатков, по одному на идентификатор: одна вставка
+/// не может обновить строку каталога дважды
+fn catalog(input: &[Stock]) -> Vec<SupplierItem> {
+    let mut items: HashMap<uuid::Uuid, SupplierItem> = HashMap::new();
+    for stock in input {
+        items
+            .entry(stock.item_id)
+            .and_modify(|item| {
+                item.first_seen = item.first_seen.min(stock.updated);
+                item.last_seen = item.last_seen.max(stock.updated);
+            })
+            .or_insert_with(|| SupplierItem::from(stock));
+    }
+    items.into_values().collect()
 }
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::{
+    models::{MailSender, Supplier, SupplierInput},
+    AppError, Result,
+};
+
+const COLUMNS: &str = "code, name, emails, contacts, source, lead_time_days, unnamed_attachments, enabled, created, updated";
+
+#[derive(Clone)]
+pub struct SupplierStorage {
+    pool: sqlx::PgPool,
+}
+
+impl SupplierStorage {
+    pub fn new(pool: sqlx::PgPool) -> SupplierStorage {
+        SupplierStorage { pool }
+    }
+    pub async fn list(&self) -> Result<Vec<Supplier>> {
+        let query = format!("SELECT {COLUMNS} FROM suppliers ORDER BY code");
+        let results = sqlx::query_as::<_, Supplier>(&query)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+    pub async fn get(&self, code: &str) -> Result<Option<Supplier>> {
+        let query = format!("SELECT {COLUMNS} FROM suppliers WHERE code = $1");
+        let result = sqlx::query_as::<_, Supplier>(&query)
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(result)
+    }
+    pub async fn create(&self, code: &str, input: &SupplierInput) -> Result<Supplier> {
+        let query = format!(
+            "INSERT INTO suppliers(code, name, emails, contacts, source, lead_time_days, unnamed_attachments, enabled) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (code) DO NOTHING RETURNING {COLUMNS}"
+        );
+        sqlx::query_as::<_, Supplier>(&query)
+            .bind(code)
+            .bind(&input.name)
+            .bind(emails(&input.emails))
+            .bind(&input.contacts)
+            .bind(&input.source)
+            .bind(input.lead_time_days)
+            .bind(input.unnamed_attachments)
+            .bind(input.enabled)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(AppError::Conflict(format!("Поставщик {code} уже есть")))
+    }
+    pub async fn update(&self, code: &str, input: &SupplierInput) -> Result<Option<Supplier>> {
+        let query = format!(
+            "UPDATE suppliers SET name = $2, emails = $3, contacts = $4, source = $5, \
+             lead_time_days = $6, unnamed_attachments = $7, enabled = $8, updated = now() WHERE code = $1 RETURNING {COLUMNS}"
+        );
+        let result = sqlx::query_as::<_, Supplier>(&query)
+            .bind(code)
+            .bind(&input.name)
+            .bind(emails(&input.emails))
+            .bind(&input.contacts)
+            .bind(&input.source)
+            .bind(input.lead_time_days)
+            .bind(input.unnamed_attachments)
+            .bind(input.enabled)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(result)
+    }
+    /// Удаляет поставщика, у которого нет остатков. Поставщика с остатками
+    /// можно только отключить.
+    pub async fn delete(&self, code: &str) -> Result<bool> {
+        let query = "DELETE FROM suppliers WHERE code = $1";
+        match sqlx::query(query).bind(code).execute(&self.pool).await {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(AppError::Conflict(format!(
+                    "У поставщика {code} есть остатки, его можно только отключить"
+                )))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+    /// Включенные поставщики по адресам, с которых приходят их письма
+    pub async fn senders(&self) -> Result<HashMap<String, MailSender>> {
+        let query = "SELECT code, emails, unnamed_attachments FROM suppliers WHERE enabled";
+        let rows = sqlx::query_as::<_, (String, Vec<String>, bool)>(query)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut senders = HashMap::new();
+        for (supplier, emails, unnamed_attachments) in rows {
+            for email in emails {
+                let sender = MailSender {
+                    supplier: supplier.clone(),
+                    unnamed_attachments,
+                };
+                senders.insert(email, sender);
+            }
+        }
+        Ok(senders)
+    }
+    /// Сроки поставки в рабочих днях у поставщиков, для которых они указаны
+    pub async fn lead_times(&self) -> Result<HashMap<String, u32>> {
+        let query = "SELECT code, lead_time_days FROM suppliers WHERE lead_time_days IS NOT NULL";
+        let rows = sqlx::query_as::<_, (String, i32)>(query)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(code, days)| (code, days.max(0) as u32))
+            .collect())
+    }
+    /// Коды отключенных поставщиков
+    pub async fn disabled(&self) -> Result<Vec<String>> {
+        let query = "SELECT code FROM suppliers WHERE NOT enabled";
+        let results = sqlx::query_scalar::<_, String>(query)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(results)
+    }
+}
+
+/// Адреса строчными буквами без пробелов, как их сравнивает почтовый клиент
+fn emails(input: &[String]) -> Vec<String> {
+    input
+        .iter()
+        .map(|e| e.trim().to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
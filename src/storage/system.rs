@@ -0,0 +1,22 @@
+use crate::Result;
+
+#[derive(Clone)]
+pub struct SystemStorage {
+    pool: sqlx::PgPool,
+}
+
+impl SystemStorage {
+    pub fn new(pool: sqlx::PgPool) -> SystemStorage {
+        SystemStorage { pool }
+    }
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+    /// Версия последней примененной миграции
+    pub async fn migration_version(&self) -> Result<Option<i64>> {
+        let query = "SELECT MAX(version) FROM _sqlx_migrations WHERE success";
+        let version: Option<i64> = sqlx::query_scalar(query).fetch_one(&self.pool).await?;
+        Ok(version)
+    }
+}
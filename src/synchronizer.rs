@@ -1,37 +1,64 @@
 use anyhow::Result;
 use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
 use tracing::info;
 
 use crate::{
+    availability::Availability,
+    config::SyncConfig,
+    health::Status,
+    metrics,
     models::Stock,
-    storage::StockStorage,
-    utils::{convert_to_create, convert_to_update, get_quantity, MsData, WooData},
+    storage::{StockStorage, SupplierStorage},
+    utils::{convert_to_create, convert_to_update, MsData, WooData},
 };
 use rust_moysklad as ms;
 use rust_woocommerce as woo;
 const STOCK_ATTRIBUTE_NAME: &str = "Наличие";
-const IN_STOCK: &str = "В наличии (2-3 раб. дня)";
-const OUT_OF_STOCK: &str = "Под заказ (5-8 недель)";
+const MS: &str = "moysklad";
+const WOO: &str = "woocommerce";
 
 pub struct Synchronizer {
     ms_client: Arc<ms::MoySkladApiClient>,
-    safira_client: Arc<woo::ApiClient>,
+    safira_client: Option<Arc<woo::ApiClient>>,
     stock_storage: Arc<StockStorage>,
+    supplier_storage: Arc<SupplierStorage>,
+    trigger: Arc<Notify>,
+    config: SyncConfig,
+    status: Arc<Status>,
+    shutdown: CancellationToken,
 }
 impl Synchronizer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ms_client: Arc<ms::MoySkladApiClient>,
-        safira_client: Arc<rust_woocommerce::ApiClient>,
+        safira_client: Option<Arc<rust_woocommerce::ApiClient>>,
         stock_storage: Arc<StockStorage>,
+        supplier_storage: Arc<SupplierStorage>,
+        trigger: Arc<Notify>,
+        config: &SyncConfig,
+        status: Arc<Status>,
+        shutdown: CancellationToken,
     ) -> Arc<Self> {
         Arc::new(Self {
             ms_client,
             safira_client,
             stock_storage,
+            supplier_storage,
+            trigger,
+            config: config.clone(),
+            status,
+            shutdown,
         })
     }
-    async fn sync(self: Arc<Self>) -> Result<()> {
+    /// Одна синхронизация вне расписания. При `dry_run` изменения только
+    /// подсчитываются и пишутся в лог, в Мой Склад и WooCommerce ничего не отправляется
+    pub async fn sync_once(self: Arc<Self>, dry_run: bool) -> Result<()> {
+        self.sync(dry_run).await
+    }
+    async fn sync(self: Arc<Self>, dry_run: bool) -> Result<()> {
         let mut stock = Vec::new();
         let limit = 500;
         let mut offset = 0;
@@ -49,16 +76,36 @@ impl Synchronizer {
                 offset += limit;
             }
         }
+        // Дальше остатком считается то, что можно продать: свободный остаток и
+        // приход в ближайшие `incoming_days` дней
+        let until = self
+            .config
+            .incoming_days
+            .map(|days| chrono::Utc::now().date_naive() + chrono::Days::new(days));
+        for item in &mut stock {
+            item.stock = item.available(until);
+        }
+        let lead_times = self.supplier_storage.lead_times().await?;
+        let availability = Availability::new(&self.config, lead_times);
         info!("Получаю данные из Мой Склад");
         let ms_data = self.clone().get_ms_data().await?;
-        let safira_data = self.clone().get_woo_data().await?;
         let products = ms_data.products.values().cloned().collect::<Vec<_>>();
         info!(
             "Получено {len} продуктов из Мой Склад для обновления",
             len = products.len()
         );
-        self.clone().update_ms_stock(&stock, &products).await?;
+        self.clone()
+            .update_ms_stock(&stock, &availability, &products, dry_run)
+            .await?;
+        if self.stopping() {
+            return Ok(());
+        }
+        if self.safira_client.is_none() {
+            info!("WooCommerce не настроен, синхронизирую только Мой Склад");
+            return Ok(());
+        }
         info!("Получаю данные от safira.club");
+        let safira_data = self.clone().get_woo_data().await?;
         info!(
             "Получено {len} продуктов из Сафира для обновления",
             len = safira_data.products.len()
@@ -78,14 +125,19 @@ impl Synchronizer {
                     &ms_data,
                     &safira_data,
                     &current_stock,
+                    &availability,
                 ) {
                     products_to_update.push(converted)
                 }
             } else {
                 // create woo product
-                if let Some(converted) =
-                    convert_to_create(&ms_product, &ms_data, &safira_data, &current_stock)
-                {
+                if let Some(converted) = convert_to_create(
+                    &ms_product,
+                    &ms_data,
+                    &safira_data,
+                    &current_stock,
+                    &availability,
+                ) {
                     products_to_create.push(converted)
                 }
             }
@@ -103,61 +155,110 @@ impl Synchronizer {
             }
         }
 
+        if dry_run {
+            info!(
+                "Пробный запуск, в safira.club не отправляю: создать {}, обновить {}, удалить {} позиций",
+                products_to_create.len(),
+                products_to_update.len(),
+                products_to_delete.len()
+            );
+            return Ok(());
+        }
         let mut count = 0;
 
+        if self.stopping() {
+            return Ok(());
+        }
         if !products_to_create.is_empty() {
             info!(
                 "Получено {} позиций для создания в safira.club",
                 products_to_create.len()
             );
-            let result = self
-                .safira_client
-                .batch_create::<woo::Product, _>(products_to_create)
+            let result = metrics::get()
+                .track(
+                    WOO,
+                    "batch_create",
+                    self.safira()?
+                        .batch_create::<woo::Product, _>(products_to_create),
+                )
                 .await?;
             info!("Создано {len} позиций в safira.club", len = result.len());
+            metrics::get().sync_products(WOO, "created", result.len());
             count += result.len();
         } else {
             info!("Нет позиций для создания в safira.club");
+            metrics::get().sync_products(WOO, "created", 0);
+        }
+        if self.stopping() {
+            return Ok(());
         }
         if !products_to_update.is_empty() {
             info!(
                 "Получено {} позиций для обновления в safira.club",
                 products_to_update.len()
             );
-            let result: Vec<woo::Product> =
-                self.safira_client.batch_update(products_to_update).await?;
+            let result: Vec<woo::Product> = metrics::get()
+                .track(
+                    WOO,
+                    "batch_update",
+                    self.safira()?.batch_update(products_to_update),
+                )
+                .await?;
             info!("Обновлено {len} позиций в safira.club", len = result.len());
+            metrics::get().sync_products(WOO, "updated", result.len());
             count += result.len();
         } else {
             info!("Нет позиций для обновления в safira.club");
+            metrics::get().sync_products(WOO, "updated", 0);
+        }
+        if self.stopping() {
+            return Ok(());
         }
         if !products_to_delete.is_empty() {
             info!(
                 "Получено {} позиций для удаления в safira.club",
                 products_to_delete.len()
             );
-            let result = self
-                .safira_client
-                .batch_delete::<woo::Product>(products_to_delete)
+            let result = metrics::get()
+                .track(
+                    WOO,
+                    "batch_delete",
+                    self.safira()?
+                        .batch_delete::<woo::Product>(products_to_delete),
+                )
                 .await?;
             info!("Удалено {len} позиций в safira.club", len = result.len());
+            metrics::get().sync_products(WOO, "deleted", result.len());
             count += result.len();
         } else {
             info!("Нет позиций для удаления в safira.club");
+            metrics::get().sync_products(WOO, "deleted", 0);
         }
         info!("Всего позиций синхронизировано: {count}");
 
         Ok(())
     }
+    /// Выставляет «Наличие» по оценке доставки. Значения, которых нет ни у одного
+    /// товара в Мой Склад, взять неоткуда: такие товары пропускаются с предупреждением
     async fn update_ms_stock(
         self: Arc<Self>,
         stock: &[Stock],
+        availability: &Availability,
         products: &[rust_moysklad::Product],
+        dry_run: bool,
     ) -> Result<()> {
-        let in_stock_attribute = get_stock_attribute(products, IN_STOCK)
-            .ok_or(anyhow::anyhow!("Не найден атрибут В наличии"))?;
-        let out_of_stock_attribute = get_stock_attribute(products, OUT_OF_STOCK)
-            .ok_or(anyhow::anyhow!("Не найден атрибут Нет в наличии"))?;
+        let mut attributes = HashMap::new();
+        for text in availability.texts() {
+            match get_stock_attribute(products, text) {
+                Some(attribute) => {
+                    attributes.insert(text.to_string(), attribute);
+                }
+                None => tracing::warn!("Не найдено значение «{STOCK_ATTRIBUTE_NAME}»: {text}"),
+            }
+        }
+        if attributes.is_empty() {
+            anyhow::bail!("Не найдено ни одного значения «{STOCK_ATTRIBUTE_NAME}»");
+        }
         let products_to_update = products
             .iter()
             .flat_map(|ms_product| {
@@ -171,38 +272,51 @@ impl Synchronizer {
                     rust_moysklad::AttributeValue::Custom(v) => v.name,
                     _ => String::new(),
                 };
-                let is_in_stock = get_quantity(&ms_sku, stock) > 2.0;
-                if !is_in_stock && value != OUT_OF_STOCK {
-                    let upd = rust_moysklad::Product::update()
-                        .meta(meta)
-                        .attribute(out_of_stock_attribute.clone())
-                        .build();
-                    Some(upd)
-                } else if is_in_stock && value != IN_STOCK {
-                    let upd = rust_moysklad::Product::update()
-                        .meta(meta)
-                        .attribute(in_stock_attribute.clone())
-                        .build();
-                    Some(upd)
-                } else {
-                    None
+                let estimate = availability.estimate(&ms_sku, stock);
+                if value == estimate.text {
+                    return None;
                 }
+                let Some(attribute) = attributes.get(&estimate.text) else {
+                    tracing::warn!(
+                        "Наличие {ms_sku} не обновлено: нет значения «{STOCK_ATTRIBUTE_NAME}» {}",
+                        estimate.text
+                    );
+                    return None;
+                };
+                let upd = rust_moysklad::Product::update()
+                    .meta(meta)
+                    .attribute(attribute.clone())
+                    .build();
+                Some(upd)
             })
             .collect::<Vec<_>>();
+        if dry_run {
+            tracing::info!(
+                "Пробный запуск, в Мой Склад не отправляю {} изменений наличия",
+                products_to_update.len()
+            );
+            return Ok(());
+        }
+        if self.stopping() {
+            return Ok(());
+        }
         if !products_to_update.is_empty() {
             tracing::info!(
                 "Получилось {} продуктов для обновления в Мой Склад",
                 products_to_update.len()
             );
-            let updated: Vec<rust_moysklad::Product> = self
-                .clone()
-                .ms_client
-                .clone()
-                .batch_create_update(products_to_update)
+            let updated: Vec<rust_moysklad::Product> = metrics::get()
+                .track(
+                    MS,
+                    "batch_create_update",
+                    self.ms_client.batch_create_update(products_to_update),
+                )
                 .await?;
             tracing::info!("Обновлено {} продуктов в Мой Склад", updated.len());
+            metrics::get().sync_products(MS, "updated", updated.len());
         } else {
-            tracing::info!("Наличие в Мой Склад актуально")
+            tracing::info!("Наличие в Мой Склад актуально");
+            metrics::get().sync_products(MS, "updated", 0);
         }
         Ok(())
     }
@@ -248,31 +362,50 @@ impl Synchronizer {
         })
     }
     async fn woo_products(self: Arc<Self>) -> Result<Vec<woo::Product>> {
-        let result = self.safira_client.list_all().await?;
+        let result = metrics::get()
+            .track(WOO, "list_product", self.safira()?.list_all())
+            .await?;
         Ok(result)
     }
     async fn woo_attributes(self: Arc<Self>) -> Result<Vec<woo::Attribute>> {
-        let result = self.safira_client.list_all().await?;
+        let result = metrics::get()
+            .track(WOO, "list_attribute", self.safira()?.list_all())
+            .await?;
         Ok(result)
     }
     async fn woo_categories(self: Arc<Self>) -> Result<Vec<woo::Category>> {
-        let result = self.safira_client.list_all().await?;
+        let result = metrics::get()
+            .track(WOO, "list_category", self.safira()?.list_all())
+            .await?;
         Ok(result)
     }
+    fn safira(&self) -> Result<&woo::ApiClient> {
+        self.safira_client
+            .as_deref()
+            .ok_or(anyhow::anyhow!("WooCommerce не настроен"))
+    }
     async fn ms_currencies(&self) -> Result<Vec<ms::Currency>> {
-        let result = self.ms_client.get_all::<ms::Currency>().await?;
+        let result = metrics::get()
+            .track(MS, "get_currency", self.ms_client.get_all::<ms::Currency>())
+            .await?;
         Ok(result)
     }
     async fn ms_countries(&self) -> Result<Vec<ms::Country>> {
-        let result = self.ms_client.get_all::<ms::Country>().await?;
+        let result = metrics::get()
+            .track(MS, "get_country", self.ms_client.get_all::<ms::Country>())
+            .await?;
         Ok(result)
     }
     async fn ms_uoms(&self) -> Result<Vec<ms::Uom>> {
-        let result = self.ms_client.get_all::<ms::Uom>().await?;
+        let result = metrics::get()
+            .track(MS, "get_uom", self.ms_client.get_all::<ms::Uom>())
+            .await?;
         Ok(result)
     }
     async fn ms_products(&self) -> Result<Vec<ms::Product>> {
-        let result = self.ms_client.get_all::<ms::Product>().await?;
+        let result = metrics::get()
+            .track(MS, "get_product", self.ms_client.get_all::<ms::Product>())
+            .await?;
         Ok(result)
     }
     // async fn updated_ms_products(&self, last_update: chrono::NaiveDateTime) -> Result<Vec<ms::Product>> {
@@ -281,21 +414,52 @@ impl Synchronizer {
     //     let result = self.ms_client.filter::<ms::Product>("updated", fo, lu).await?;
     //     Ok(result)
     // }
-    pub async fn run(self: Arc<Self>) {
-        while let Err(e) = self.clone().sync().await {
-            tracing::error!("Ошибка синхронизации: --> {e:?}");
-            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    /// Синхронизация с записью результата для `/health`
+    async fn sync_recorded(self: Arc<Self>) -> Result<()> {
+        let timer = metrics::get().sync_timer();
+        let result = self.clone().sync(false).await;
+        timer.finish(result.is_ok());
+        match &result {
+            Ok(()) => self.status.job_success("sync").await,
+            Err(e) => self.status.job_failure("sync", e).await,
         }
-        loop {
+        result
+    }
+    pub async fn run(self: Arc<Self>) {
+        while !self.shutdown.is_cancelled() {
             tracing::info!("Начинаю синхронизацию");
-            if let Err(e) = self.clone().sync().await {
-                tracing::error!("{e:?}");
-                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+            let secs = if let Err(e) = self.clone().sync_recorded().await {
+                tracing::error!("Ошибка синхронизации: --> {e:?}");
+                60
             } else {
                 tracing::info!("Сайт синхронизирован");
-                tokio::time::sleep(tokio::time::Duration::from_secs(6 * 60 * 60)).await;
+                self.config.interval_hours * 60 * 60
+            };
+            if self.clone().wait(secs).await {
+                break;
             }
         }
+        tracing::info!("Синхронизация остановлена");
+    }
+    /// Ждет до следующей синхронизации, досрочно просыпается по внешнему запросу.
+    /// Возвращает `true`, если сервис останавливается
+    async fn wait(self: Arc<Self>, secs: u64) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(secs)) => false,
+            _ = self.trigger.notified() => {
+                tracing::info!("Получен запрос на внеочередную синхронизацию");
+                false
+            }
+            _ = self.shutdown.cancelled() => true,
+        }
+    }
+    /// Сервис останавливается: текущий пакет уже отправлен, следующий не начинаем
+    fn stopping(&self) -> bool {
+        let stopping = self.shutdown.is_cancelled();
+        if stopping {
+            tracing::info!("Синхронизация прервана остановкой сервиса");
+        }
+        stopping
     }
 }
 fn get_stock_attribute(
@@ -7,18 +7,54 @@ use chrono::Days;
 use rust_moysklad as ms;
 use rust_woocommerce as woo;
 use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 
-use crate::models::Stock;
+use crate::{availability::Availability, models::Stock};
 
-pub async fn pause(hours: u64) {
+pub async fn pause(hours: u64, shutdown: &CancellationToken) -> bool {
     let secs = 60 * 60 * hours;
-    tokio::time::sleep(tokio::time::Duration::from_secs(secs)).await;
+    sleep_or_cancel(secs, shutdown).await
+}
+/// Ждет SIGINT (Ctrl+C) или SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Ошибка ожидания Ctrl+C: {e:?}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Ошибка подписки на SIGTERM: {e:?}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+/// Пауза, которая прерывается остановкой сервиса. Возвращает `true`, если сервис останавливается
+pub async fn sleep_or_cancel(secs: u64, shutdown: &CancellationToken) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(tokio::time::Duration::from_secs(secs)) => false,
+        _ = shutdown.cancelled() => true,
+    }
 }
 pub fn convert_to_create(
     ms_product: &ms::Product,
     ms_data: &MsData,
     woo_data: &WooData,
     stock: &[Stock],
+    availability: &Availability,
 ) -> Option<impl Serialize + Clone + Send + Sync + 'static> {
     let sku = ms_product.article.as_ref()?.to_uppercase();
     let quantity = get_quantity(&sku, stock) as i32;
@@ -170,6 +206,9 @@ pub fn convert_to_create(
         .meta_data("_woo_uom_input", format!("/{uom}"))
         .meta_data("_alg_wc_pq_min", format!("{min_quantity:.2}"))
         .meta_data("_alg_wc_pq_step", format!("{quantity_step:.2}"));
+    for (key, value) in delivery_meta(&sku, stock, availability) {
+        result.meta_data(key, value);
+    }
     let result = result.build();
     Some(result)
 }
@@ -180,6 +219,7 @@ pub fn convert_to_update(
     ms_data: &MsData,
     woo_data: &WooData,
     stock: &[Stock],
+    availability: &Availability,
 ) -> Option<impl Serialize + Clone + Send + Sync + 'static> {
     if let Some(last_upd) = ms_product.updated {
         let now = chrono::Local::now().naive_local();
@@ -362,6 +402,9 @@ pub fn convert_to_update(
         .meta_data("_woo_uom_input", format!("/{uom}"))
         .meta_data("_alg_wc_pq_min", format!("{min_quantity:.2}"))
         .meta_data("_alg_wc_pq_step", format!("{quantity_step:.2}"));
+    for (key, value) in delivery_meta(&sku, stock, availability) {
+        result.meta_data(key, value);
+    }
     let result = result.build();
     Some(result)
 }
@@ -467,18 +510,51 @@ impl Display for PriceTag {
         }
     }
 }
+/// Оценка доставки для темы сайта: значение «Наличие» и срок в рабочих днях,
+/// пустой для товара под заказ
+fn delivery_meta(
+    sku: &str,
+    stock: &[Stock],
+    availability: &Availability,
+) -> [(&'static str, String); 2] {
+    let estimate = availability.estimate(sku, stock);
+    let days = estimate
+        .lead_days
+        .map(|d| d.to_string())
+        .unwrap_or_default();
+    [
+        ("_delivery_estimate", estimate.text),
+        ("_delivery_days", days),
+    ]
+}
+/// Остаток товара с артикулом Мой Склад `sku`. Если у поставщиков есть позиции
+/// с таким же артикулом, берутся они, иначе позиции, в наименовании которых
+/// встречаются все слова артикула.
 pub fn get_quantity(sku: &str, stock: &[Stock]) -> f64 {
-    let mut temp = stock.to_vec();
+    matching_stock(sku, stock).iter().map(|s| s.stock).sum()
+}
+/// Строки остатков артикула: с тем же артикулом поставщика, а если таких нет,
+/// со всеми словами артикула в наименовании
+pub fn matching_stock<'a>(sku: &str, stock: &'a [Stock]) -> Vec<&'a Stock> {
+    let by_article = stock
+        .iter()
+        .filter(|s| {
+            s.article
+                .as_deref()
+                .is_some_and(|a| a.trim().eq_ignore_ascii_case(sku.trim()))
+        })
+        .collect::<Vec<_>>();
+    if !by_article.is_empty() {
+        return by_article;
+    }
+    let mut temp = stock.iter().collect::<Vec<_>>();
     for word in sku.split_whitespace() {
-        temp = temp
-            .into_iter()
-            .filter(|s| {
-                s.name
-                    .replace(',', ".")
-                    .to_uppercase()
-                    .contains(&word.replace(',', ".").to_uppercase())
-            })
-            .collect::<Vec<_>>();
+        temp.retain(|s| {
+            s.name
+                .replace(',', ".")
+                .to_uppercase()
+                .contains(&word.replace(',', ".").to_uppercase())
+        });
     }
-    temp.iter().map(|s| s.stock).sum::<f64>()
+    temp
 }